@@ -0,0 +1,26 @@
+CREATE TABLE IF NOT EXISTS "apc_reading" (
+    "uuid" BLOB NOT NULL PRIMARY KEY,
+    "created_on" TEXT NOT NULL,
+    "measurement_time" TEXT NOT NULL,
+    "location" TEXT NOT NULL,
+    "device_sn" TEXT NOT NULL,
+    "tvoc" INTEGER NOT NULL,
+    "eco2" INTEGER NOT NULL,
+    "aqi" INTEGER NOT NULL,
+    "temperature" INTEGER NOT NULL,
+    "humidity" INTEGER NOT NULL,
+    "pm1_0" INTEGER NOT NULL,
+    "pm2_5" INTEGER NOT NULL,
+    "pm10" INTEGER NOT NULL,
+    "pm1_0_in_air" INTEGER NOT NULL,
+    "pm2_5_in_air" INTEGER NOT NULL,
+    "pm10_in_air" INTEGER NOT NULL,
+    "um0_3_particles" INTEGER NOT NULL,
+    "um0_5_particles" INTEGER NOT NULL,
+    "um1_particles" INTEGER NOT NULL,
+    "um2_5_particles" INTEGER NOT NULL,
+    "um5_particles" INTEGER NOT NULL,
+    "um10_particles" INTEGER NOT NULL
+);
+
+CREATE INDEX "measurement_time_dev_index" ON "apc_reading" ("measurement_time", "device_sn");
@@ -0,0 +1,263 @@
+//! The configuration file, as written by `apc1 init`.
+//!
+//! Values in the configuration file are used when the equivalent command-line
+//! argument is not provided.
+//!
+//! A single sensor is described by the top-level `i2c_device` and `location`.
+//! When more than one is attached, each is instead listed in a `[[sensors]]`
+//! table, which may be on an I2C bus or a serial port:
+//!
+//! ```toml
+//! interval = 60
+//! db_uri = "sqlite:///var/lib/apc1/readings.db"
+//!
+//! [[sensors]]
+//! i2c_device = "/dev/i2c-1"
+//! location = "office"
+//!
+//! [[sensors]]
+//! serial_device = "/dev/ttyUSB0"
+//! location = "bedroom"
+//! interval = 300
+//! ```
+//!
+//! The top-level `interval` may be left out when every sensor sets its own,
+//! and `db_uri` is only needed to log to or export from a database. To log
+//! to InfluxDB instead, set `sink` and describe the bucket; the API token is
+//! read from the `APC1_INFLUXDB_TOKEN` environment variable:
+//!
+//! ```toml
+//! sink = "influxdb"
+//!
+//! [influxdb]
+//! url = "http://localhost:8086"
+//! org = "home"
+//! bucket = "air"
+//! ```
+use std::{
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{device::Connection, sink::SinkKind, systemd};
+
+/// The path `apc1 init` suggests writing the configuration to.
+pub const DEFAULT_PATH: &str = "/etc/apc1/config.toml";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Full path to the i2c device the sensor is attached to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub i2c_device: Option<PathBuf>,
+    /// Identifies where the device is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// How frequently (in seconds) to record a measurement, for sensors
+    /// that don't set their own interval.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<NonZeroU64>,
+    /// The URI of the database to log measurements to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub db_uri: Option<String>,
+    /// Where `apc1 log` writes measurements; a database if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sink: Option<SinkKind>,
+    /// The InfluxDB bucket to log to when `sink` is `influxdb`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub influxdb: Option<InfluxDb>,
+    /// Every sensor to read from, in place of `i2c_device` and `location`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sensors: Vec<Sensor>,
+    /// Settings for the units generated by `apc1 systemd`.
+    #[serde(default, skip_serializing_if = "systemd::Settings::is_default")]
+    pub systemd: systemd::Settings,
+}
+
+/// The `[influxdb]` table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InfluxDb {
+    /// The base URL of the server, for example `http://localhost:8086`.
+    pub url: String,
+    /// The organization; not needed for InfluxDB 1.x.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
+    /// The bucket to write to.
+    pub bucket: String,
+}
+
+/// A sensor listed in the `[[sensors]]` tables.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sensor {
+    /// Either `i2c_device` or `serial_device`, with the path to the device.
+    #[serde(flatten)]
+    pub connection: Connection,
+    /// Identifies where the device is.
+    pub location: String,
+    /// How frequently (in seconds) to record a measurement, if it differs
+    /// from the top-level interval.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<NonZeroU64>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read configuration from {}", path.display()))?;
+        let config: Self = toml::from_str(&contents)
+            .with_context(|| format!("Invalid configuration in {}", path.display()))?;
+        if config.i2c_device.is_some() && !config.sensors.is_empty() {
+            anyhow::bail!(
+                "Invalid configuration in {}: set either i2c_device or [[sensors]], not both",
+                path.display()
+            );
+        }
+        Ok(config)
+    }
+
+    /// The path of every device the configured sensors are attached to.
+    pub fn devices(&self) -> Vec<&Path> {
+        self.i2c_device
+            .iter()
+            .map(PathBuf::as_path)
+            .chain(
+                self.sensors
+                    .iter()
+                    .filter_map(|sensor| sensor.connection.path()),
+            )
+            .collect()
+    }
+
+    /// The interval each configured sensor is read at, or `None` if a
+    /// sensor has no interval of its own and there is no top-level one.
+    pub fn intervals(&self) -> Option<Vec<NonZeroU64>> {
+        if self.sensors.is_empty() {
+            Some(vec![self.interval?])
+        } else {
+            self.sensors
+                .iter()
+                .map(|sensor| sensor.interval.or(self.interval))
+                .collect()
+        }
+    }
+
+    /// Write the configuration to the given path, creating any missing parent directories.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(path, toml::to_string(self)?)
+            .with_context(|| format!("Failed to write configuration to {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn multiple_sensors() {
+        let config: Config = toml::from_str(
+            r#"
+            interval = 60
+            db_uri = "sqlite:///var/lib/apc1/readings.db"
+
+            [[sensors]]
+            i2c_device = "/dev/i2c-1"
+            location = "office"
+
+            [[sensors]]
+            serial_device = "/dev/ttyUSB0"
+            location = "bedroom"
+            interval = 300
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.sensors,
+            [
+                Sensor {
+                    connection: Connection::I2cDevice("/dev/i2c-1".into()),
+                    location: "office".to_string(),
+                    interval: None,
+                },
+                Sensor {
+                    connection: Connection::SerialDevice("/dev/ttyUSB0".into()),
+                    location: "bedroom".to_string(),
+                    interval: NonZeroU64::new(300),
+                },
+            ]
+        );
+        assert_eq!(
+            config.devices(),
+            [Path::new("/dev/i2c-1"), Path::new("/dev/ttyUSB0")]
+        );
+        let saved: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(saved.sensors, config.sensors);
+        assert_eq!(
+            config.intervals(),
+            Some(vec![
+                NonZeroU64::new(60).unwrap(),
+                NonZeroU64::new(300).unwrap()
+            ])
+        );
+    }
+
+    #[test]
+    fn sensor_intervals_stand_alone() {
+        let config: Config = toml::from_str(
+            r#"
+            [[sensors]]
+            i2c_device = "/dev/i2c-1"
+            location = "office"
+            interval = 30
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.interval, None);
+        assert_eq!(config.db_uri, None);
+        assert_eq!(config.intervals(), Some(vec![NonZeroU64::new(30).unwrap()]));
+
+        let config: Config = toml::from_str(r#"i2c_device = "/dev/i2c-1""#).unwrap();
+        assert_eq!(config.intervals(), None);
+    }
+
+    #[test]
+    fn influxdb_and_mock() {
+        let config: Config = toml::from_str(
+            r#"
+            interval = 60
+            sink = "influxdb"
+
+            [influxdb]
+            url = "http://localhost:8086"
+            bucket = "air"
+
+            [[sensors]]
+            location = "desk"
+            mock = {}
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.sink, Some(SinkKind::Influxdb));
+        assert_eq!(
+            config.influxdb,
+            Some(InfluxDb {
+                url: "http://localhost:8086".to_string(),
+                org: None,
+                bucket: "air".to_string(),
+            })
+        );
+        assert_eq!(config.sensors[0].connection, Connection::Mock {});
+        let saved: Config = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(saved.sensors, config.sensors);
+        assert_eq!(saved.influxdb, config.influxdb);
+    }
+}
@@ -0,0 +1,300 @@
+//! Long-running measurement loops which survive sensor and database hiccups.
+//!
+//! Each sensor is read on its own blocking thread and its measurements are
+//! handed to an async consumer over a channel. An invalid reading is retried
+//! after about a second and a failing bus or serial port with exponential
+//! backoff. The log writer holds measurements in memory while its sink is
+//! unavailable and retries them periodically, and SIGTERM or SIGINT stops
+//! reading and lets the consumer finish with what it has before exiting.
+use std::{collections::VecDeque, future::Future, sync::mpsc::RecvTimeoutError, time::Duration};
+
+use apc1_core::Measurement;
+use time::OffsetDateTime;
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver, Sender};
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::{
+    device::{self, Device},
+    sink::MeasurementSink,
+};
+
+/// The most measurements held while the sink is unavailable. Beyond this,
+/// the oldest are dropped.
+const BACKLOG_LIMIT: usize = 10_000;
+
+/// How often to retry writing the backlog while no new measurements arrive.
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The delay before the first retry after an I2C failure.
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The longest delay between retries after repeated I2C failures.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A sensor to read, along with what its consumer needs to handle its measurements.
+pub struct Sensor<C> {
+    /// Identifies the sensor in log messages.
+    pub location: String,
+    pub dev: Device,
+    pub interval: Duration,
+    pub context: C,
+}
+
+/// Read each sensor every interval and pass its measurements to a consumer
+/// until a shutdown signal is received or the consumers stop.
+///
+/// `consume` is called once per sensor, with that sensor's context (such as
+/// a sink labelled for it) and the receiving end of its measurements.
+///
+/// systemd is notified when the service is ready, after each successful
+/// reading for the watchdog, and when it begins shutting down.
+pub async fn run<C, F, Fut>(sensors: Vec<Sensor<C>>, mut consume: F) -> anyhow::Result<()>
+where
+    F: FnMut(C, Receiver<(OffsetDateTime, Measurement)>) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    let mut stops = vec![];
+    let mut readers = vec![];
+    let mut consumers = vec![];
+    for sensor in sensors {
+        let span = tracing::info_span!("sensor", location = sensor.location);
+        let (sender, receiver) = mpsc::channel(64);
+        // Dropping `stop` wakes the reader from its sleep and ends it.
+        let (stop, stopped) = std::sync::mpsc::channel::<()>();
+        stops.push(stop);
+        consumers.push(tokio::spawn(
+            consume(sensor.context, receiver).instrument(span.clone()),
+        ));
+        readers.push(tokio::task::spawn_blocking(move || {
+            span.in_scope(|| read_sensor(sensor.dev, sensor.interval, sender, stopped))
+        }));
+    }
+    let signals = tokio::spawn(async move {
+        match shutdown_signal().await {
+            Ok(()) => tracing::info!("Shutting down"),
+            Err(e) => tracing::error!(error=?e, "Unable to listen for signals; shutting down"),
+        }
+        let _ = sd_notify::notify(false, &[sd_notify::NotifyState::Stopping]);
+        drop(stops);
+    });
+    let _ = sd_notify::notify(false, &[sd_notify::NotifyState::Ready]);
+
+    for reader in readers {
+        reader.await?;
+    }
+    signals.abort();
+    for consumer in consumers {
+        consumer.await??;
+    }
+    Ok(())
+}
+
+/// Wait for SIGTERM or SIGINT.
+async fn shutdown_signal() -> anyhow::Result<()> {
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => {},
+    }
+    Ok(())
+}
+
+/// Read a measurement every interval until `stopped` is signalled or the
+/// receiving end of `dest` is dropped.
+fn read_sensor(
+    mut dev: Device,
+    interval: Duration,
+    dest: Sender<(OffsetDateTime, Measurement)>,
+    stopped: std::sync::mpsc::Receiver<()>,
+) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let delay = match device::read_measurement(&mut dev) {
+            Ok(measurement) => {
+                tracing::debug!("Read measurement successfully");
+                backoff = MIN_BACKOFF;
+                let measurement_time = OffsetDateTime::now_utc();
+                // Waiting for room could block shutdown behind a stuck consumer.
+                match dest.try_send((measurement_time, measurement)) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => {
+                        tracing::warn!("The consumer is falling behind; dropped a measurement");
+                    }
+                    Err(TrySendError::Closed(_)) => {
+                        tracing::error!("Measurements are no longer being consumed; stopping");
+                        return;
+                    }
+                }
+                // Only reached with a working sensor, so the watchdog catches a wedged device.
+                let _ = sd_notify::notify(false, &[sd_notify::NotifyState::Watchdog]);
+                interval
+            }
+            Err(e) => retry_delay(&e, &mut backoff),
+        };
+        match stopped.recv_timeout(delay) {
+            Err(RecvTimeoutError::Timeout) => continue,
+            _ => return,
+        }
+    }
+}
+
+/// How long to wait before reading again after `error`, doubling `backoff`
+/// if the bus or serial port failed.
+fn retry_delay(error: &anyhow::Error, backoff: &mut Duration) -> Duration {
+    // The bus works, but the reading was bad; the next one likely won't be.
+    if device::response_error(error).is_some() {
+        tracing::warn!(error=?error, "Measurement reading was invalid");
+        return Duration::from_millis(1100);
+    }
+    let delay = *backoff;
+    *backoff = (*backoff * 2).min(MAX_BACKOFF);
+    tracing::warn!(error=?error, retry_in=?delay, "Failed to read from the sensor");
+    delay
+}
+
+/// Write each measurement received to the sink until the sender is dropped.
+///
+/// Each measurement is given an ID as it's received. Those that can't be
+/// written are kept, oldest first, and retried when the next measurement
+/// arrives, every [`RETRY_INTERVAL`], and once more before returning.
+pub async fn write_measurements(
+    sink: Box<dyn MeasurementSink>,
+    mut receiver: Receiver<(OffsetDateTime, Measurement)>,
+) -> anyhow::Result<()> {
+    let mut backlog = VecDeque::new();
+    loop {
+        tokio::select! {
+            received = receiver.recv() => {
+                let Some((measurement_time, measurement)) = received else {
+                    break;
+                };
+                if backlog.len() == BACKLOG_LIMIT {
+                    backlog.pop_front();
+                    tracing::warn!(
+                        limit = BACKLOG_LIMIT,
+                        "Too many measurements waiting to be written; dropped the oldest"
+                    );
+                }
+                backlog.push_back((Uuid::new_v4(), measurement_time, measurement));
+                flush(sink.as_ref(), &mut backlog).await;
+            }
+            _ = tokio::time::sleep(RETRY_INTERVAL), if !backlog.is_empty() => {
+                flush(sink.as_ref(), &mut backlog).await;
+            }
+        }
+    }
+
+    if !flush(sink.as_ref(), &mut backlog).await {
+        tracing::error!(
+            count = backlog.len(),
+            "Exiting with measurements that could not be written"
+        );
+    }
+    Ok(())
+}
+
+/// Write the backlog to the sink, oldest first, stopping at the first failure.
+///
+/// Returns whether the backlog was emptied.
+async fn flush(
+    sink: &dyn MeasurementSink,
+    backlog: &mut VecDeque<(Uuid, OffsetDateTime, Measurement)>,
+) -> bool {
+    while let Some((id, measurement_time, measurement)) = backlog.front() {
+        if let Err(e) = sink.write(*id, measurement_time, measurement).await {
+            tracing::warn!(
+                error=?e,
+                pending = backlog.len(),
+                "Failed to write measurement; will retry"
+            );
+            return false;
+        }
+        tracing::info!("Logged measurement successfully");
+        backlog.pop_front();
+    }
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use apc1_embedded::Apc1Sensor;
+    use apc1_mock::{MockI2c, Simulator};
+
+    use super::*;
+
+    /// A sink which fails a given number of writes before accepting them.
+    struct FlakySink {
+        failures: Mutex<usize>,
+        attempted: std::sync::Arc<Mutex<Vec<Uuid>>>,
+        written: std::sync::Arc<Mutex<Vec<(Uuid, OffsetDateTime)>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl MeasurementSink for FlakySink {
+        async fn write(
+            &self,
+            id: Uuid,
+            measurement_time: &OffsetDateTime,
+            _measurement: &Measurement,
+        ) -> anyhow::Result<()> {
+            self.attempted.lock().unwrap().push(id);
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                anyhow::bail!("database unavailable");
+            }
+            self.written.lock().unwrap().push((id, *measurement_time));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn backlog_is_written_in_order_once_the_sink_recovers() {
+        let attempted = std::sync::Arc::new(Mutex::new(vec![]));
+        let written = std::sync::Arc::new(Mutex::new(vec![]));
+        let sink = FlakySink {
+            failures: Mutex::new(3),
+            attempted: attempted.clone(),
+            written: written.clone(),
+        };
+        let (sender, receiver) = mpsc::channel(8);
+        let times = (0..4)
+            .map(|i| OffsetDateTime::from_unix_timestamp(i).unwrap())
+            .collect::<Vec<_>>();
+        for time in &times {
+            let measurement = Simulator::new().readings;
+            sender.send((*time, measurement)).await.unwrap();
+        }
+        drop(sender);
+
+        write_measurements(Box::new(sink), receiver).await.unwrap();
+
+        let written = written.lock().unwrap();
+        let written_times = written.iter().map(|(_, time)| *time).collect::<Vec<_>>();
+        assert_eq!(written_times, times);
+        // Every attempt at the first measurement reused its ID.
+        let first_id = written[0].0;
+        assert!(attempted.lock().unwrap()[..4]
+            .iter()
+            .all(|id| *id == first_id));
+    }
+
+    #[test]
+    fn invalid_readings_are_retried_without_backoff() {
+        let mut simulator = Simulator::new();
+        simulator.corrupt_checksums(1);
+        let mut dev = Device::I2c(Apc1Sensor::new(MockI2c::new(simulator)));
+        let mut backoff = MIN_BACKOFF;
+
+        let e = device::read_measurement(&mut dev).unwrap_err();
+        assert_eq!(retry_delay(&e, &mut backoff), Duration::from_millis(1100));
+        assert_eq!(backoff, MIN_BACKOFF);
+
+        let e = anyhow::anyhow!("the bus went away");
+        assert_eq!(retry_delay(&e, &mut backoff), MIN_BACKOFF);
+        assert_eq!(backoff, MIN_BACKOFF * 2);
+    }
+}
@@ -0,0 +1,407 @@
+//! Access to an APC1 connected via the Linux i2c-dev driver or a serial port,
+//! or to a simulated one.
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use apc1_core::{i2c, uart, Measurement, Module};
+use apc1_embedded::{Apc1Sensor, Apc1UartSensor, MeasurementMode, RetryPolicy};
+use apc1_mock::{MockUart, Simulator};
+use embedded_hal::{
+    delay::DelayNs,
+    i2c::{ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation},
+};
+use embedded_io_adapters::std::FromStd;
+use i2cdev::core::{I2CMessage, I2CTransfer};
+use i2cdev::linux::{LinuxI2CDevice, LinuxI2CMessage};
+use serde::{Deserialize, Serialize};
+
+/// How long to wait for the UART variant to respond to a request.
+const SERIAL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to wait for the I2C variant to start up after it is reset, in
+/// milliseconds.
+const I2C_READY_TIMEOUT_MS: u32 = 5_000;
+
+/// How to retry reading the module ID while the device starts up.
+pub const MODULE_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 12,
+    delay_ms: 250,
+};
+
+/// How to retry reading a measurement when a single reading is wanted.
+pub const MEASUREMENT_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 12,
+    delay_ms: 1_100,
+};
+
+impl<I2C> Device<I2C> {
+    /// Whether [`open`] reset the sensor. Only the APC1-I has a reset command.
+    pub fn was_reset(&self) -> bool {
+        matches!(self, Device::I2c(_))
+    }
+}
+
+/// Where a sensor is attached.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Connection {
+    /// The i2c-dev device file for the bus the APC1-I is on, such as `/dev/i2c-1`.
+    I2cDevice(PathBuf),
+    /// The serial port the APC1-U is on, such as `/dev/ttyUSB0`.
+    SerialDevice(PathBuf),
+    /// A simulated APC1-U, for trying things out without a sensor. It is
+    /// written as `mock = {}` in the configuration file.
+    Mock {},
+}
+
+impl Connection {
+    /// The device file, unless the sensor is simulated.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::I2cDevice(path) | Self::SerialDevice(path) => Some(path),
+            Self::Mock {} => None,
+        }
+    }
+}
+
+impl Display for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path() {
+            Some(path) => path.display().fmt(f),
+            None => write!(f, "simulated sensor"),
+        }
+    }
+}
+
+/// An open connection to an APC1.
+///
+/// `I2C` is the bus an APC1-I is on, which is only something other than an
+/// i2c-dev device file in tests.
+pub enum Device<I2C = LinuxI2c> {
+    I2c(Apc1Sensor<I2C>),
+    Serial(Apc1UartSensor<SerialPort>),
+    Mock(Apc1UartSensor<MockUart>),
+}
+
+/// A serial port, adapted to the [`embedded_io`] traits the
+/// [`Apc1UartSensor`] driver is built on.
+pub type SerialPort = FromStd<Box<dyn serialport::SerialPort>>;
+
+/// Open the device and put it in a known state.
+///
+/// An APC1-I is reset; see [`Device::was_reset`].
+pub async fn open(connection: &Connection) -> anyhow::Result<Device> {
+    match connection {
+        Connection::I2cDevice(path) => open_i2c(path).await,
+        Connection::SerialDevice(path) => open_serial(path),
+        Connection::Mock {} => Ok(Device::Mock(Apc1UartSensor::new(MockUart::new(
+            Simulator::new(),
+        )))),
+    }
+}
+
+/// Whether an APC1-I answers on the I2C bus.
+///
+/// Only a measurement is read, and nothing is written, so whatever else is on
+/// the bus is left alone. A sensor reporting a fault still counts.
+pub fn probe_i2c(i2c_device: &Path) -> bool {
+    let Ok(dev) = LinuxI2CDevice::new(i2c_device, i2c::DEVICE_ADDR.into()) else {
+        return false;
+    };
+    matches!(
+        Apc1Sensor::new(LinuxI2c(dev)).measurement(),
+        Ok(_) | Err(apc1_embedded::Error::Response(apc1_core::Error::Device(_)))
+    )
+}
+
+/// Open the I2C device and reset it so it is in a known state.
+///
+/// A sensor that isn't ready once the timeout passes, such as one reporting
+/// a fault, is still returned so the fault can be seen when it is read.
+async fn open_i2c(i2c_device: &Path) -> anyhow::Result<Device> {
+    let dev = LinuxI2CDevice::new(i2c_device, i2c::DEVICE_ADDR.into())
+        .with_context(|| "Unable to open the I2C device file. Is the i2c-dev module loaded?")?;
+
+    let sensor = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+        let mut sensor = Apc1Sensor::new(LinuxI2c(dev))
+            .reset()
+            .map_err(|(_, e)| e)
+            .with_context(|| "Failed to write the reset command")?;
+        if let Err(e) = sensor.wait_ready(&mut StdDelay, I2C_READY_TIMEOUT_MS) {
+            tracing::warn!(error=?e, "The sensor isn't ready after being reset");
+        }
+        Ok(sensor)
+    })
+    .await??;
+
+    Ok(Device::I2c(sensor))
+}
+
+/// Open the serial port and switch the device to sending measurements on request.
+fn open_serial(serial_device: &Path) -> anyhow::Result<Device> {
+    let port = serialport::new(serial_device.to_string_lossy(), uart::BAUD_RATE.into())
+        .data_bits(serialport::DataBits::Eight)
+        .parity(serialport::Parity::None)
+        .stop_bits(serialport::StopBits::One)
+        .timeout(SERIAL_TIMEOUT)
+        .open()
+        .with_context(|| format!("Unable to open the serial port {}", serial_device.display()))?;
+    let mut sensor = Apc1UartSensor::new(FromStd::new(port));
+    sensor
+        .set_measurement_mode(MeasurementMode::Passive)
+        .map_err(driver_error)
+        .with_context(|| "Failed to write the passive measurement command")?;
+    // Drop anything the device sent before it switched modes.
+    std::thread::sleep(Duration::from_millis(100));
+    let port = sensor.release().into_inner();
+    port.clear(serialport::ClearBuffer::Input)?;
+
+    Ok(Device::Serial(Apc1UartSensor::new(FromStd::new(port))))
+}
+
+pub fn read_module<I2C: I2c>(dev: &mut Device<I2C>) -> anyhow::Result<Module>
+where
+    I2C::Error: Send + Sync + 'static,
+{
+    match dev {
+        Device::I2c(sensor) => sensor
+            .module()
+            .map_err(driver_error)
+            .with_context(|| "Failed to read the module ID"),
+        Device::Serial(sensor) => sensor
+            .module()
+            .map_err(driver_error)
+            .with_context(|| "Failed to read the module ID"),
+        Device::Mock(sensor) => sensor
+            .module()
+            .map_err(driver_error)
+            .with_context(|| "Failed to read the module ID"),
+    }
+}
+
+pub fn read_measurement<I2C: I2c>(dev: &mut Device<I2C>) -> anyhow::Result<Measurement>
+where
+    I2C::Error: Send + Sync + 'static,
+{
+    match dev {
+        Device::I2c(sensor) => sensor
+            .measurement()
+            .map_err(driver_error)
+            .with_context(|| "Failed to read a measurement"),
+        Device::Serial(sensor) => sensor
+            .request_measurement()
+            .map_err(driver_error)
+            .with_context(|| "Failed to read a measurement"),
+        Device::Mock(sensor) => sensor
+            .request_measurement()
+            .map_err(driver_error)
+            .with_context(|| "Failed to read a measurement"),
+    }
+}
+
+/// Read the module ID, retrying failures as `policy` allows.
+pub fn read_module_with_retry<I2C: I2c>(
+    dev: &mut Device<I2C>,
+    policy: &RetryPolicy,
+) -> anyhow::Result<Module>
+where
+    I2C::Error: Send + Sync + 'static,
+{
+    retry(policy, || read_module(dev))
+}
+
+/// Read a measurement, retrying failures as `policy` allows.
+pub fn read_measurement_with_retry<I2C: I2c>(
+    dev: &mut Device<I2C>,
+    policy: &RetryPolicy,
+) -> anyhow::Result<Measurement>
+where
+    I2C::Error: Send + Sync + 'static,
+{
+    match dev {
+        Device::I2c(sensor) => sensor
+            .measurement_with_retry(policy, &mut StdDelay)
+            .map_err(driver_error)
+            .with_context(|| "Failed to read a measurement"),
+        dev => retry(policy, || read_measurement(dev)),
+    }
+}
+
+/// The invalid response or fault the device reported, if that's why `error` happened.
+pub fn response_error(error: &anyhow::Error) -> Option<&apc1_core::Error> {
+    error.downcast_ref()
+}
+
+/// Convert an error from the driver, passing on invalid responses and faults
+/// as the [`apc1_core::Error`] itself so [`response_error`] finds them.
+///
+/// anyhow only downcasts to the error it was given, not to its sources.
+fn driver_error<E>(error: apc1_embedded::Error<E>) -> anyhow::Error
+where
+    E: std::fmt::Debug + Send + Sync + 'static,
+{
+    match error {
+        apc1_embedded::Error::Response(e) => e.into(),
+        e => e.into(),
+    }
+}
+
+/// Call `read` until it succeeds, the device reports a fault, or `policy`
+/// runs out of attempts, as [`Apc1Sensor::measurement_with_retry`] does.
+fn retry<T>(
+    policy: &RetryPolicy,
+    mut read: impl FnMut() -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let mut attempts = 1;
+    loop {
+        match read() {
+            Err(e)
+                if attempts < policy.max_attempts
+                    && !matches!(response_error(&e), Some(apc1_core::Error::Device(_))) =>
+            {
+                tracing::warn!(error=?e, "Failed to read from the device; trying again");
+                attempts += 1;
+                std::thread::sleep(Duration::from_millis(policy.delay_ms.into()));
+            }
+            result => return result,
+        }
+    }
+}
+
+/// An i2c-dev device file, adapted to the [`embedded_hal`] traits the
+/// [`Apc1Sensor`] driver is built on.
+pub struct LinuxI2c(LinuxI2CDevice);
+
+/// An error from the i2c-dev driver.
+#[derive(Debug)]
+pub struct I2cError(std::io::Error);
+
+impl embedded_hal::i2c::Error for I2cError {
+    /// Classify the error by its errno, as described in the kernel's
+    /// `Documentation/i2c/fault-codes.rst`.
+    fn kind(&self) -> ErrorKind {
+        const EIO: i32 = 5;
+        const ENXIO: i32 = 6;
+        const EAGAIN: i32 = 11;
+        const EPROTO: i32 = 71;
+        const ETIMEDOUT: i32 = 110;
+        const EREMOTEIO: i32 = 121;
+
+        match self.0.raw_os_error() {
+            Some(ENXIO | EREMOTEIO) => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown),
+            Some(EAGAIN) => ErrorKind::ArbitrationLoss,
+            Some(EIO | EPROTO | ETIMEDOUT) => ErrorKind::Bus,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl ErrorType for LinuxI2c {
+    type Error = I2cError;
+}
+
+impl embedded_hal::i2c::I2c for LinuxI2c {
+    /// Carry out the operations in a single transfer. The address is ignored,
+    /// since the device file is already bound to the sensor's address.
+    fn transaction(
+        &mut self,
+        _address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut messages = operations
+            .iter_mut()
+            .map(|operation| match operation {
+                Operation::Read(buf) => LinuxI2CMessage::read(buf),
+                Operation::Write(bytes) => LinuxI2CMessage::write(bytes),
+            })
+            .collect::<Vec<_>>();
+        self.0
+            .transfer(&mut messages)
+            .map(|_| ())
+            .map_err(|e| I2cError(e.into()))
+    }
+}
+
+/// Waits by blocking the thread.
+struct StdDelay;
+
+impl DelayNs for StdDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        std::thread::sleep(Duration::from_nanos(ns.into()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use apc1_core::DeviceErrorCode;
+    use apc1_mock::MockI2c;
+
+    use super::*;
+
+    fn mock_i2c(simulator: Simulator) -> Device<MockI2c> {
+        Device::I2c(Apc1Sensor::new(MockI2c::new(simulator)))
+    }
+
+    #[tokio::test]
+    async fn mock() {
+        let mut dev = open(&Connection::Mock {}).await.unwrap();
+        assert_eq!(read_module(&mut dev).unwrap().fw_version_minor, 35);
+        assert_eq!(read_measurement(&mut dev).unwrap().eco2, 429);
+
+        let mut simulator = Simulator::new();
+        simulator.corrupt_checksums(1);
+        let mut dev: Device = Device::Mock(Apc1UartSensor::new(MockUart::new(simulator)));
+        let e = read_measurement(&mut dev).unwrap_err();
+        assert!(matches!(
+            response_error(&e),
+            Some(apc1_core::Error::Checksum { .. })
+        ));
+        assert!(read_measurement(&mut dev).is_ok());
+    }
+
+    #[test]
+    fn i2c_response_errors() {
+        let mut simulator = Simulator::new();
+        simulator.corrupt_checksums(1);
+        let e = read_measurement(&mut mock_i2c(simulator)).unwrap_err();
+        assert!(matches!(
+            response_error(&e),
+            Some(apc1_core::Error::Checksum { .. })
+        ));
+
+        let mut simulator = Simulator::new();
+        simulator.faults = DeviceErrorCode::from_bits(0x08);
+        let e = read_measurement(&mut mock_i2c(simulator)).unwrap_err();
+        assert!(matches!(
+            response_error(&e),
+            Some(apc1_core::Error::Device(_))
+        ));
+    }
+
+    #[test]
+    fn retry_stops_at_faults() {
+        let mut simulator = Simulator::new();
+        simulator.faults = DeviceErrorCode::from_bits(0x08);
+        let mut dev = mock_i2c(simulator);
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            delay_ms: 0,
+        };
+
+        let mut attempts = 0;
+        let result = retry(&policy, || {
+            attempts += 1;
+            read_measurement(&mut dev)
+        });
+
+        assert!(matches!(
+            response_error(&result.unwrap_err()),
+            Some(apc1_core::Error::Device(_))
+        ));
+        assert_eq!(attempts, 1);
+    }
+}
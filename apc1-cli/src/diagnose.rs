@@ -0,0 +1,339 @@
+//! A health check for an installed sensor.
+//!
+//! The sensor is read several times and passes if it reports no faults, few
+//! of its responses are corrupt, and every value it reports is within the
+//! range given in the datasheet.
+use std::{fmt::Display, time::Duration};
+
+use apc1_core::{DeviceErrorCode, DeviceFault, Measurement, Module};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::device::{self, Device};
+
+/// The largest share of reads that may fail their checksum for the sensor to pass.
+const MAX_CHECKSUM_FAILURE_RATE: f64 = 0.1;
+
+/// A value in a measurement, how to get it, and its range in the datasheet.
+type Range = (&'static str, fn(&Measurement) -> u16, u16, u16);
+
+/// The range of each value, from Section 8.2.1 of the APC1 datasheet.
+///
+/// The datasheet gives 0-50C for the temperatures, in units of 0.1C, so a
+/// sensor below freezing is reported as out of range: it isn't specified to
+/// work there.
+const RANGES: [Range; 13] = [
+    ("pm1_0", |m| m.pm1_0, 0, 500),
+    ("pm2_5", |m| m.pm2_5, 0, 1_000),
+    ("pm10", |m| m.pm10, 0, 1_500),
+    ("pm1_0_in_air", |m| m.pm1_0_in_air, 0, 500),
+    ("pm2_5_in_air", |m| m.pm2_5_in_air, 0, 1_000),
+    ("pm10_in_air", |m| m.pm10_in_air, 0, 1_500),
+    ("tvoc", |m| m.tvoc, 0, 65_000),
+    ("eco2", |m| m.eco2, 400, 65_000),
+    ("t_comp", |m| m.t_comp, 0, 500),
+    ("rh_comp", |m| m.rh_comp, 0, 1_000),
+    ("t_raw", |m| m.t_raw, 0, 500),
+    ("rh_raw", |m| m.rh_raw, 0, 1_000),
+    ("aqi", |m| m.aqi.into(), 1, 5),
+];
+
+/// How to print the report.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// A summary for people to read.
+    #[default]
+    Text,
+    /// A single JSON object.
+    Json,
+}
+
+/// Whether the device reported a fault in any reading.
+#[derive(Debug, Serialize)]
+pub struct FaultCheck {
+    pub fault: String,
+    pub reported: bool,
+}
+
+/// A value outside the range given in the datasheet.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct OutOfRange {
+    pub field: &'static str,
+    pub value: u16,
+    pub min: u16,
+    pub max: u16,
+}
+
+/// The outcome of the health check.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub name_and_type: String,
+    pub serial_number: u64,
+    pub firmware_version: String,
+    /// Whether the sensor was reset before it was read; only the APC1-I can be.
+    pub reset: bool,
+    pub faults: Vec<FaultCheck>,
+    /// The number of measurements requested.
+    pub reads: u32,
+    /// The number of valid measurements received.
+    pub measurements: u32,
+    pub checksum_failures: u32,
+    /// Reads that failed for any other reason, such as a bus error.
+    pub read_errors: u32,
+    /// Values outside the datasheet's range; only the first is kept for each field.
+    pub out_of_range: Vec<OutOfRange>,
+    pub passed: bool,
+}
+
+impl Report {
+    pub fn checksum_failure_rate(&self) -> f64 {
+        f64::from(self.checksum_failures) / f64::from(self.reads.max(1))
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Module:             {}", self.name_and_type)?;
+        writeln!(f, "Serial number:      {}", self.serial_number)?;
+        writeln!(f, "Firmware version:   {}", self.firmware_version)?;
+        if self.reset {
+            writeln!(f, "Reset:              yes")?;
+        } else {
+            writeln!(
+                f,
+                "Reset:              no, the sensor was checked as it was"
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Device faults:")?;
+        for check in &self.faults {
+            let state = if check.reported { "FAULT" } else { "ok" };
+            writeln!(f, "  {:<38}{state}", check.fault)?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Measurements:       {} of {} read",
+            self.measurements, self.reads
+        )?;
+        writeln!(
+            f,
+            "Checksum failures:  {} ({:.1}%)",
+            self.checksum_failures,
+            self.checksum_failure_rate() * 100.0
+        )?;
+        writeln!(f, "Other read errors:  {}", self.read_errors)?;
+        if self.out_of_range.is_empty() {
+            writeln!(f, "Datasheet ranges:   ok")?;
+        } else {
+            for (index, value) in self.out_of_range.iter().enumerate() {
+                let label = if index == 0 { "Datasheet ranges:" } else { "" };
+                writeln!(
+                    f,
+                    "{label:<20}{} was {}, expected {} to {}",
+                    value.field, value.value, value.min, value.max
+                )?;
+            }
+        }
+        writeln!(f)?;
+        let result = if self.passed { "PASS" } else { "FAIL" };
+        writeln!(f, "Result:             {result}")
+    }
+}
+
+/// The results of the readings so far.
+struct Diagnosis {
+    module: Module,
+    reset: bool,
+    faults: DeviceErrorCode,
+    reads: u32,
+    measurements: u32,
+    checksum_failures: u32,
+    read_errors: u32,
+    out_of_range: Vec<OutOfRange>,
+}
+
+impl Diagnosis {
+    fn new(module: Module, reset: bool) -> Self {
+        Self {
+            module,
+            reset,
+            faults: DeviceErrorCode::default(),
+            reads: 0,
+            measurements: 0,
+            checksum_failures: 0,
+            read_errors: 0,
+            out_of_range: vec![],
+        }
+    }
+
+    fn record(&mut self, result: anyhow::Result<Measurement>) {
+        self.reads += 1;
+        let measurement = match result {
+            Ok(measurement) => measurement,
+            Err(e) => {
+                match device::response_error(&e) {
+                    Some(apc1_core::Error::Device(code)) => {
+                        self.faults = DeviceErrorCode::from_bits(self.faults.bits() | code.bits());
+                    }
+                    Some(apc1_core::Error::Checksum { .. }) => self.checksum_failures += 1,
+                    _ => {
+                        tracing::debug!(error = ?e, "Failed to read a measurement");
+                        self.read_errors += 1;
+                    }
+                }
+                return;
+            }
+        };
+
+        self.measurements += 1;
+        for (field, value, min, max) in RANGES {
+            let value = value(&measurement);
+            let reported = self.out_of_range.iter().any(|range| range.field == field);
+            if !(min..=max).contains(&value) && !reported {
+                self.out_of_range.push(OutOfRange {
+                    field,
+                    value,
+                    min,
+                    max,
+                });
+            }
+        }
+    }
+
+    fn into_report(self) -> Report {
+        let mut report = Report {
+            name_and_type: self.module.name_and_type.to_string(),
+            serial_number: self.module.serial_number,
+            firmware_version: format!(
+                "{}.{}",
+                self.module.fw_version_major, self.module.fw_version_minor
+            ),
+            reset: self.reset,
+            faults: DeviceFault::ALL
+                .map(|fault| FaultCheck {
+                    fault: fault.to_string(),
+                    reported: self.faults.contains(fault),
+                })
+                .into(),
+            reads: self.reads,
+            measurements: self.measurements,
+            checksum_failures: self.checksum_failures,
+            read_errors: self.read_errors,
+            out_of_range: self.out_of_range,
+            passed: false,
+        };
+        report.passed = report.measurements > 0
+            && self.faults.is_empty()
+            && report.out_of_range.is_empty()
+            && report.checksum_failure_rate() <= MAX_CHECKSUM_FAILURE_RATE;
+        report
+    }
+}
+
+/// Take `samples` measurements, `interval` apart, and report on the sensor's health.
+///
+/// `reset` is whether the sensor was reset when it was opened.
+pub fn diagnose(
+    dev: &mut Device,
+    module: Module,
+    reset: bool,
+    samples: u32,
+    interval: Duration,
+) -> Report {
+    let mut diagnosis = Diagnosis::new(module, reset);
+    for sample in 0..samples {
+        if sample > 0 {
+            std::thread::sleep(interval);
+        }
+        diagnosis.record(device::read_measurement(dev));
+    }
+    diagnosis.into_report()
+}
+
+#[cfg(test)]
+mod test {
+    use apc1_embedded::{Apc1Sensor, Apc1UartSensor};
+    use apc1_mock::{MockI2c, MockUart, Simulator};
+
+    use super::*;
+
+    fn mock(simulator: Simulator) -> Device {
+        Device::Mock(Apc1UartSensor::new(MockUart::new(simulator)))
+    }
+
+    #[test]
+    fn healthy_sensor_passes() {
+        let mut simulator = Simulator::new();
+        let module = simulator.module.clone();
+        simulator.corrupt_checksums(1);
+        let mut dev = mock(simulator);
+
+        let report = diagnose(&mut dev, module, false, 10, Duration::ZERO);
+
+        assert_eq!(report.measurements, 9);
+        assert_eq!(report.checksum_failures, 1);
+        assert!(report.passed);
+        let text = report.to_string();
+        assert!(text.contains("Firmware version:   0.35\n"));
+        assert!(text.contains("Reset:              no, the sensor was checked as it was\n"));
+        assert!(text.contains("Checksum failures:  1 (10.0%)\n"));
+        assert!(text.ends_with("Result:             PASS\n"));
+    }
+
+    #[test]
+    fn faults_and_ranges_fail() {
+        let mut simulator = Simulator::new();
+        simulator.readings.eco2 = 300;
+        let mut dev = mock(simulator.clone());
+        let module = device::read_module(&mut dev).unwrap();
+        let mut diagnosis = Diagnosis::new(module, true);
+
+        diagnosis.record(device::read_measurement(&mut dev));
+        diagnosis.record(device::read_measurement(&mut dev));
+        simulator.faults = DeviceErrorCode::from_bits(0x10);
+        diagnosis.record(device::read_measurement(&mut mock(simulator)));
+        let report = diagnosis.into_report();
+
+        assert!(!report.passed);
+        assert_eq!(
+            report.out_of_range,
+            [OutOfRange {
+                field: "eco2",
+                value: 300,
+                min: 400,
+                max: 65_000
+            }]
+        );
+        let text = report.to_string();
+        assert!(text.contains("  laser error                           FAULT\n"));
+        assert!(text.contains("  fan stopped                           ok\n"));
+        assert!(text.contains("Datasheet ranges:   eco2 was 300, expected 400 to 65000\n"));
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["passed"], false);
+        assert_eq!(json["measurements"], 2);
+        assert_eq!(json["reset"], true);
+    }
+
+    #[test]
+    fn i2c_faults_and_checksums() {
+        let mut simulator = Simulator::new();
+        let mut diagnosis = Diagnosis::new(simulator.module.clone(), true);
+        let mut corrupt = simulator.clone();
+        corrupt.corrupt_checksums(1);
+        let mut dev = Device::I2c(Apc1Sensor::new(MockI2c::new(corrupt)));
+
+        diagnosis.record(device::read_measurement(&mut dev));
+        diagnosis.record(device::read_measurement(&mut dev));
+        simulator.faults = DeviceErrorCode::from_bits(0x08);
+        let mut dev = Device::I2c(Apc1Sensor::new(MockI2c::new(simulator)));
+        diagnosis.record(device::read_measurement(&mut dev));
+        let report = diagnosis.into_report();
+
+        assert!(!report.passed);
+        assert_eq!(report.checksum_failures, 1);
+        assert_eq!(report.read_errors, 0);
+        let text = report.to_string();
+        assert!(text.contains("  fan stopped                           FAULT\n"));
+    }
+}
@@ -0,0 +1,382 @@
+//! Export logged readings so they can be shared and analyzed elsewhere.
+//!
+//! Readings can be exported as they were logged or averaged over fixed
+//! windows of time. Windows are aligned to UTC, so daily averages run from
+//! midnight to midnight UTC.
+use std::{collections::BTreeMap, io::Write};
+
+use clap::ValueEnum;
+use futures::TryStreamExt;
+use serde::{Serialize, Serializer};
+use time::OffsetDateTime;
+
+use crate::storage::{Database, Reading};
+
+/// How exported readings are written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Comma-separated values with a header row.
+    #[default]
+    Csv,
+    /// One JSON object per line.
+    Json,
+}
+
+/// The windows readings can be averaged over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Downsample {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Downsample {
+    fn seconds(self) -> i64 {
+        match self {
+            Self::Minute => 60,
+            Self::Hour => 60 * 60,
+            Self::Day => 24 * 60 * 60,
+        }
+    }
+
+    /// The start of the window containing the given time.
+    fn window_start(self, time: OffsetDateTime) -> OffsetDateTime {
+        let timestamp = time.unix_timestamp();
+        let start = timestamp - timestamp.rem_euclid(self.seconds());
+        OffsetDateTime::from_unix_timestamp(start).expect("window starts before the given time")
+    }
+}
+
+/// Which readings to export.
+pub struct Filter<'a> {
+    /// Only export readings from this location.
+    pub location: Option<&'a str>,
+    /// Only export readings taken at or after this time.
+    pub start: Option<OffsetDateTime>,
+    /// Only export readings taken before this time.
+    pub end: Option<OffsetDateTime>,
+}
+
+/// A number which is written without a fractional part when it has none,
+/// so readings that weren't averaged look the same as when they were logged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Number(f64);
+
+impl Serialize for Number {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0.fract() == 0.0 {
+            serializer.serialize_i64(self.0 as i64)
+        } else {
+            serializer.serialize_f64(self.0)
+        }
+    }
+}
+
+/// An exported row: a single reading, or the average of the readings from
+/// one device in a window of time.
+#[derive(Debug, Serialize)]
+struct Row {
+    /// When the reading was taken, or the start of the window.
+    #[serde(with = "time::serde::rfc3339")]
+    timestamp: OffsetDateTime,
+    location: String,
+    device_sn: String,
+    /// The number of readings in the row.
+    samples: u32,
+    /// Degrees Celsius.
+    temperature: Number,
+    /// Percent relative humidity.
+    humidity: Number,
+    aqi: Number,
+    eco2: Number,
+    tvoc: Number,
+    pm1_0: Number,
+    pm2_5: Number,
+    pm10: Number,
+    pm1_0_in_air: Number,
+    pm2_5_in_air: Number,
+    pm10_in_air: Number,
+    um_0_3_particles: Number,
+    um_0_5_particles: Number,
+    um_1_particles: Number,
+    um_2_5_particles: Number,
+    um_5_particles: Number,
+    um_10_particles: Number,
+}
+
+/// The running totals of the readings from one device in a window of time.
+struct Summary {
+    timestamp: OffsetDateTime,
+    location: String,
+    device_sn: String,
+    samples: u32,
+    /// The sum of each value, in the order of [`Row`]'s fields.
+    sums: [i64; 17],
+}
+
+impl Summary {
+    fn new(timestamp: OffsetDateTime, reading: &Reading) -> Self {
+        let mut summary = Self {
+            timestamp,
+            location: reading.location.clone(),
+            device_sn: reading.device_sn.clone(),
+            samples: 0,
+            sums: [0; 17],
+        };
+        summary.add(reading);
+        summary
+    }
+
+    fn add(&mut self, reading: &Reading) {
+        let values = [
+            reading.temperature,
+            reading.humidity,
+            reading.aqi,
+            reading.eco2,
+            reading.tvoc,
+            reading.pm1_0,
+            reading.pm2_5,
+            reading.pm10,
+            reading.pm1_0_in_air,
+            reading.pm2_5_in_air,
+            reading.pm10_in_air,
+            reading.um0_3_particles,
+            reading.um0_5_particles,
+            reading.um1_particles,
+            reading.um2_5_particles,
+            reading.um5_particles,
+            reading.um10_particles,
+        ];
+        for (sum, value) in self.sums.iter_mut().zip(values) {
+            *sum += i64::from(value);
+        }
+        self.samples += 1;
+    }
+
+    fn into_row(self) -> Row {
+        let samples = f64::from(self.samples);
+        // Fields are initialized in the order they're written, which is the order of `sums`.
+        let mut sums = self.sums.into_iter();
+        let mut average = |scale: f64| {
+            let sum = sums.next().expect("a sum for every field") as f64;
+            // Two decimal places is well beyond the sensor's precision.
+            Number((sum / samples / scale * 100.0).round() / 100.0)
+        };
+        Row {
+            timestamp: self.timestamp,
+            location: self.location,
+            device_sn: self.device_sn,
+            samples: self.samples,
+            // Stored in tenths of a degree and tenths of a percent.
+            temperature: average(10.0),
+            humidity: average(10.0),
+            aqi: average(1.0),
+            eco2: average(1.0),
+            tvoc: average(1.0),
+            pm1_0: average(1.0),
+            pm2_5: average(1.0),
+            pm10: average(1.0),
+            pm1_0_in_air: average(1.0),
+            pm2_5_in_air: average(1.0),
+            pm10_in_air: average(1.0),
+            um_0_3_particles: average(1.0),
+            um_0_5_particles: average(1.0),
+            um_1_particles: average(1.0),
+            um_2_5_particles: average(1.0),
+            um_5_particles: average(1.0),
+            um_10_particles: average(1.0),
+        }
+    }
+}
+
+/// Turns readings, oldest first, into the rows to export.
+struct Downsampler {
+    downsample: Option<Downsample>,
+    /// The window currently being summarized, by location and device.
+    window: BTreeMap<(String, String), Summary>,
+    window_start: Option<OffsetDateTime>,
+}
+
+impl Downsampler {
+    fn new(downsample: Option<Downsample>) -> Self {
+        Self {
+            downsample,
+            window: BTreeMap::new(),
+            window_start: None,
+        }
+    }
+
+    /// Add a reading, returning any rows that are complete.
+    fn push(&mut self, reading: Reading) -> Vec<Row> {
+        let Some(downsample) = self.downsample else {
+            return vec![Summary::new(reading.measurement_time, &reading).into_row()];
+        };
+
+        let window_start = downsample.window_start(reading.measurement_time);
+        let rows = if self.window_start != Some(window_start) {
+            self.window_start = Some(window_start);
+            self.finish()
+        } else {
+            vec![]
+        };
+        self.window
+            .entry((reading.location.clone(), reading.device_sn.clone()))
+            .and_modify(|summary| summary.add(&reading))
+            .or_insert_with(|| Summary::new(window_start, &reading));
+        rows
+    }
+
+    /// Return the rows for the window in progress.
+    fn finish(&mut self) -> Vec<Row> {
+        std::mem::take(&mut self.window)
+            .into_values()
+            .map(Summary::into_row)
+            .collect()
+    }
+}
+
+/// Writes rows in the requested format.
+enum Writer<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Json(W),
+}
+
+impl<W: Write> Writer<W> {
+    fn new(format: Format, output: W) -> Self {
+        match format {
+            Format::Csv => Self::Csv(Box::new(csv::Writer::from_writer(output))),
+            Format::Json => Self::Json(output),
+        }
+    }
+
+    fn write(&mut self, row: &Row) -> anyhow::Result<()> {
+        match self {
+            Self::Csv(writer) => writer.serialize(row)?,
+            Self::Json(output) => {
+                serde_json::to_writer(&mut *output, row)?;
+                writeln!(output)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        match self {
+            Self::Csv(writer) => writer.flush()?,
+            Self::Json(output) => output.flush()?,
+        }
+        Ok(())
+    }
+}
+
+/// Write the readings matching the filter to `output`, returning the number
+/// of rows written.
+pub async fn export<W: Write>(
+    db: &Database,
+    filter: &Filter<'_>,
+    downsample: Option<Downsample>,
+    format: Format,
+    output: W,
+) -> anyhow::Result<u64> {
+    let mut readings = db.readings_between(filter.location, filter.start, filter.end);
+    let mut downsampler = Downsampler::new(downsample);
+    let mut writer = Writer::new(format, output);
+    let mut written = 0;
+
+    while let Some(reading) = readings.try_next().await? {
+        for row in downsampler.push(reading) {
+            writer.write(&row)?;
+            written += 1;
+        }
+    }
+    for row in downsampler.finish() {
+        writer.write(&row)?;
+        written += 1;
+    }
+    writer.flush()?;
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod test {
+    use apc1_mock::Simulator;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn reading(timestamp: i64, device_sn: &str, pm2_5: i32) -> Reading {
+        let measurement = Simulator::new().readings;
+        let time = OffsetDateTime::from_unix_timestamp(timestamp).unwrap();
+        let mut reading = Reading::new(Uuid::new_v4(), time, "office", device_sn, &measurement);
+        reading.pm2_5 = pm2_5;
+        reading
+    }
+
+    #[test]
+    fn hourly_averages() {
+        let mut downsampler = Downsampler::new(Some(Downsample::Hour));
+        let mut rows = vec![];
+        for reading in [
+            reading(3_600, "2", 10),
+            reading(3_600, "1", 1),
+            reading(5_400, "1", 2),
+            reading(7_199, "1", 4),
+            reading(7_200, "1", 8),
+        ] {
+            rows.extend(downsampler.push(reading));
+        }
+        assert_eq!(rows.len(), 2);
+        rows.extend(downsampler.finish());
+
+        let summary = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.timestamp.unix_timestamp(),
+                    row.device_sn.as_str(),
+                    row.samples,
+                    row.pm2_5,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (3_600, "1", 3, Number(2.33)),
+                (3_600, "2", 1, Number(10.0)),
+                (7_200, "1", 1, Number(8.0)),
+            ]
+        );
+        assert_eq!(rows[0].temperature, Number(20.2));
+    }
+
+    #[test]
+    fn write_rows() {
+        let mut downsampler = Downsampler::new(None);
+        let rows = downsampler.push(reading(1_700_000_000, "42", 3));
+        let mut output = vec![];
+        let mut writer = Writer::new(Format::Csv, &mut output);
+        writer.write(&rows[0]).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "timestamp,location,device_sn,samples,temperature,humidity,aqi,eco2,tvoc,",
+                "pm1_0,pm2_5,pm10,pm1_0_in_air,pm2_5_in_air,pm10_in_air,um_0_3_particles,",
+                "um_0_5_particles,um_1_particles,um_2_5_particles,um_5_particles,um_10_particles\n",
+                "2023-11-14T22:13:20Z,office,42,1,20.2,58.1,1,429,37,0,3,0,0,0,0,336,100,20,10,0,0\n",
+            )
+        );
+
+        let mut output = vec![];
+        let mut writer = Writer::new(Format::Json, &mut output);
+        writer.write(&rows[0]).unwrap();
+        drop(writer);
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(json["pm2_5"], 3);
+        assert_eq!(json["humidity"], 58.1);
+    }
+}
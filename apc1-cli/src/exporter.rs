@@ -0,0 +1,319 @@
+//! Serve the latest measurements for Prometheus to scrape.
+use std::{net::SocketAddr, sync::Arc};
+
+use anyhow::Context;
+use apc1_core::{DeviceErrorCode, DeviceFault, Measurement};
+use axum::{extract::State, http::header::CONTENT_TYPE, routing::get, Router};
+use prometheus::{GaugeVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder};
+use tokio::task::JoinSet;
+
+use crate::{daemon::Sensor, device};
+
+/// Gauges holding the most recent measurement from each sensor.
+///
+/// Every metric carries `location` and `serial_number` labels identifying
+/// the sensor. A sensor's gauges are only exported once it has been read,
+/// so a sensor that never answers doesn't look like one reporting clean air.
+pub struct Metrics {
+    registry: Registry,
+    pm1_0: GaugeVec,
+    pm2_5: GaugeVec,
+    pm10: GaugeVec,
+    tvoc: GaugeVec,
+    eco2: GaugeVec,
+    aqi: GaugeVec,
+    temperature: GaugeVec,
+    humidity: GaugeVec,
+    device_error: GaugeVec,
+    read_errors: IntCounterVec,
+}
+
+/// The labels identifying the sensor a metric is for.
+const SENSOR_LABELS: [&str; 2] = ["location", "serial_number"];
+
+impl Metrics {
+    pub fn new() -> anyhow::Result<Self> {
+        let registry = Registry::new_custom(Some("apc1".to_string()), None)?;
+        let gauge = |name: &str, help: &str| -> anyhow::Result<GaugeVec> {
+            let gauge = GaugeVec::new(Opts::new(name, help), &SENSOR_LABELS)?;
+            registry.register(Box::new(gauge.clone()))?;
+            Ok(gauge)
+        };
+
+        let metrics = Self {
+            pm1_0: gauge(
+                "pm1_0_micrograms_per_cubic_meter",
+                "PM1.0 mass concentration",
+            )?,
+            pm2_5: gauge(
+                "pm2_5_micrograms_per_cubic_meter",
+                "PM2.5 mass concentration",
+            )?,
+            pm10: gauge("pm10_micrograms_per_cubic_meter", "PM10 mass concentration")?,
+            tvoc: gauge("tvoc_ppb", "Total volatile organic compounds")?,
+            eco2: gauge("eco2_ppm", "CO2 equivalents")?,
+            aqi: gauge(
+                "aqi",
+                "Air Quality Index according to the UBA classification of TVOC, from 1 to 5",
+            )?,
+            temperature: gauge("temperature_celsius", "Compensated temperature")?,
+            humidity: gauge("relative_humidity_percent", "Compensated relative humidity")?,
+            device_error: GaugeVec::new(
+                Opts::new("device_error", "Whether the device is reporting the fault"),
+                &["location", "serial_number", "fault"],
+            )?,
+            read_errors: IntCounterVec::new(
+                Opts::new(
+                    "read_errors_total",
+                    "Readings that could not be taken or were invalid",
+                ),
+                &SENSOR_LABELS,
+            )?,
+            registry,
+        };
+        metrics
+            .registry
+            .register(Box::new(metrics.device_error.clone()))?;
+        metrics
+            .registry
+            .register(Box::new(metrics.read_errors.clone()))?;
+
+        Ok(metrics)
+    }
+
+    /// The gauges for the sensor with the given location and serial number.
+    pub fn sensor(&self, location: &str, serial_number: u64) -> SensorMetrics {
+        let serial_number = serial_number.to_string();
+        SensorMetrics {
+            pm1_0: self.pm1_0.clone(),
+            pm2_5: self.pm2_5.clone(),
+            pm10: self.pm10.clone(),
+            tvoc: self.tvoc.clone(),
+            eco2: self.eco2.clone(),
+            aqi: self.aqi.clone(),
+            temperature: self.temperature.clone(),
+            humidity: self.humidity.clone(),
+            device_error: self.device_error.clone(),
+            read_errors: self
+                .read_errors
+                .with_label_values(&[location, serial_number.as_str()]),
+            location: location.to_string(),
+            serial_number,
+        }
+    }
+
+    /// Render the metrics in the Prometheus text format.
+    pub fn render(&self) -> anyhow::Result<String> {
+        Ok(TextEncoder::new().encode_to_string(&self.registry.gather())?)
+    }
+}
+
+/// The gauges for one sensor, from [`Metrics::sensor`].
+///
+/// Each gauge's series is created the first time it's set.
+pub struct SensorMetrics {
+    pm1_0: GaugeVec,
+    pm2_5: GaugeVec,
+    pm10: GaugeVec,
+    tvoc: GaugeVec,
+    eco2: GaugeVec,
+    aqi: GaugeVec,
+    temperature: GaugeVec,
+    humidity: GaugeVec,
+    device_error: GaugeVec,
+    read_errors: IntCounter,
+    location: String,
+    serial_number: String,
+}
+
+impl SensorMetrics {
+    /// Update the gauges with a new measurement.
+    pub fn record(&self, measurement: &Measurement) {
+        let set = |gauge: &GaugeVec, value: f64| {
+            gauge
+                .with_label_values(&[self.location.as_str(), self.serial_number.as_str()])
+                .set(value);
+        };
+        set(&self.pm1_0, measurement.pm1_0.into());
+        set(&self.pm2_5, measurement.pm2_5.into());
+        set(&self.pm10, measurement.pm10.into());
+        set(&self.tvoc, measurement.tvoc.into());
+        set(&self.eco2, measurement.eco2.into());
+        set(&self.aqi, measurement.aqi.into());
+        // Scale in f64 so the gauges aren't left with f32 rounding error.
+        set(
+            &self.temperature,
+            f64::from(measurement.temperature().decidegrees_celsius()) / 10.0,
+        );
+        set(
+            &self.humidity,
+            f64::from(measurement.humidity().per_mille()) / 10.0,
+        );
+        // The device only sends a measurement when it has no faults.
+        self.record_faults(DeviceErrorCode::from_bits(0));
+    }
+
+    /// Update the fault gauges with the faults the device reported.
+    pub fn record_faults(&self, code: DeviceErrorCode) {
+        for fault in DeviceFault::ALL {
+            let labels = [
+                self.location.as_str(),
+                self.serial_number.as_str(),
+                fault_label(fault),
+            ];
+            self.device_error
+                .with_label_values(&labels)
+                .set(if code.contains(fault) { 1.0 } else { 0.0 });
+        }
+    }
+
+    /// Count a failed reading, and record the faults if the device reported any.
+    pub fn record_error(&self, error: &anyhow::Error) {
+        self.read_errors.inc();
+        if let Some(apc1_core::Error::Device(code)) = device::response_error(error) {
+            self.record_faults(*code);
+        }
+    }
+}
+
+/// The value of the `fault` label for each fault.
+fn fault_label(fault: DeviceFault) -> &'static str {
+    match fault {
+        DeviceFault::TooManyFanRestarts => "too_many_fan_restarts",
+        DeviceFault::FanSpeedLow => "fan_speed_low",
+        DeviceFault::Photodiode => "photodiode",
+        DeviceFault::FanStopped => "fan_stopped",
+        DeviceFault::Laser => "laser",
+        DeviceFault::VocSensor => "voc_sensor",
+        DeviceFault::TemperatureHumiditySensor => "temperature_humidity_sensor",
+    }
+}
+
+/// Read each sensor every interval and serve the metrics on `listen` until an error occurs.
+pub async fn run(
+    sensors: Vec<Sensor<SensorMetrics>>,
+    listen: SocketAddr,
+    metrics: Metrics,
+) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("Unable to listen on {listen}"))?;
+    tracing::info!(%listen, "Serving metrics at /metrics");
+
+    let app = Router::new()
+        .route("/metrics", get(render))
+        .with_state(Arc::new(metrics));
+    let server = tokio::spawn(async move { axum::serve(listener, app).await });
+    let mut sensor_readers = JoinSet::new();
+    for sensor in sensors {
+        let span = tracing::info_span!("sensor", location = sensor.location);
+        sensor_readers.spawn_blocking(move || span.in_scope(|| read_sensor(sensor)));
+    }
+
+    tokio::select! {
+        result = server => result?.with_context(|| "Metrics server failed"),
+        Some(result) = sensor_readers.join_next() => result?,
+    }
+}
+
+async fn render(
+    State(metrics): State<Arc<Metrics>>,
+) -> Result<([(axum::http::HeaderName, &'static str); 1], String), axum::http::StatusCode> {
+    let body = metrics.render().map_err(|e| {
+        tracing::error!(error=?e, "Failed to render metrics");
+        axum::http::StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(([(CONTENT_TYPE, prometheus::TEXT_FORMAT)], body))
+}
+
+fn read_sensor(sensor: Sensor<SensorMetrics>) -> anyhow::Result<()> {
+    let Sensor {
+        mut dev,
+        interval,
+        context: metrics,
+        ..
+    } = sensor;
+    loop {
+        match device::read_measurement(&mut dev) {
+            Ok(measurement) => metrics.record(&measurement),
+            Err(e) => {
+                tracing::warn!(error=?e, "Measurement reading was invalid");
+                metrics.record_error(&e);
+            }
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use apc1_embedded::Apc1Sensor;
+    use apc1_mock::{MockI2c, Simulator};
+
+    use super::*;
+    use crate::device::Device;
+
+    /// Find the value of the named metric with the given labels, which may
+    /// be in any order.
+    fn sample<'a>(rendered: &'a str, name: &str, labels: &[&str]) -> Option<&'a str> {
+        rendered
+            .lines()
+            .filter_map(|line| line.strip_prefix(name)?.strip_prefix('{'))
+            .filter_map(|line| line.split_once("} "))
+            .find(|(line_labels, _)| {
+                labels
+                    .iter()
+                    .all(|label| line_labels.split(',').any(|l| l == *label))
+            })
+            .map(|(_, value)| value)
+    }
+
+    #[test]
+    fn render() {
+        let mut simulator = Simulator::new();
+        let measurement = simulator.readings.clone();
+        let metrics = Metrics::new().unwrap();
+        let office = metrics.sensor("office", 42);
+        let bedroom = metrics.sensor("bedroom", 43);
+        office.record(&measurement);
+        simulator.faults = DeviceErrorCode::from_bits(0x08);
+        let mut dev = Device::I2c(Apc1Sensor::new(MockI2c::new(simulator)));
+        office.record_error(&device::read_measurement(&mut dev).unwrap_err());
+        bedroom.read_errors.inc();
+
+        let rendered = metrics.render().unwrap();
+        let office = ["location=\"office\"", "serial_number=\"42\""];
+        let bedroom = ["location=\"bedroom\"", "serial_number=\"43\""];
+        assert_eq!(sample(&rendered, "apc1_eco2_ppm", &office), Some("429"));
+        assert_eq!(sample(&rendered, "apc1_eco2_ppm", &bedroom), None);
+        assert_eq!(
+            sample(&rendered, "apc1_temperature_celsius", &office),
+            Some("20.2")
+        );
+        assert_eq!(
+            sample(
+                &rendered,
+                "apc1_device_error",
+                &[office[0], office[1], "fault=\"fan_stopped\""]
+            ),
+            Some("1")
+        );
+        assert_eq!(
+            sample(
+                &rendered,
+                "apc1_device_error",
+                &[office[0], office[1], "fault=\"laser\""]
+            ),
+            Some("0")
+        );
+        assert_eq!(sample(&rendered, "apc1_device_error", &bedroom), None);
+        assert_eq!(
+            sample(&rendered, "apc1_read_errors_total", &office),
+            Some("1")
+        );
+        assert_eq!(
+            sample(&rendered, "apc1_read_errors_total", &bedroom),
+            Some("1")
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! Write measurements to InfluxDB with the line protocol.
+//!
+//! This uses the `/api/v2/write` endpoint, which InfluxDB 2.x provides and
+//! InfluxDB 1.8 and later support for compatibility. With 1.x, use the
+//! database name (optionally followed by `/` and the retention policy) as the
+//! bucket and `username:password` as the token.
+use std::{fmt::Write, time::Duration};
+
+use anyhow::Context;
+use apc1_core::Measurement;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::sink::MeasurementSink;
+
+/// The InfluxDB measurement name readings are written to.
+const MEASUREMENT: &str = "apc1";
+
+/// How long to wait to connect to the server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for a write to complete, so an unresponsive server can't
+/// hold up the backlog or shutdown indefinitely.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub struct InfluxDbSink {
+    client: reqwest::Client,
+    /// The full URL of the write endpoint, including the query string.
+    write_url: reqwest::Url,
+    token: Option<String>,
+    location: String,
+    device_id: String,
+}
+
+impl InfluxDbSink {
+    pub fn new(
+        url: &str,
+        org: Option<&str>,
+        bucket: &str,
+        token: Option<String>,
+        location: String,
+        device_id: String,
+    ) -> anyhow::Result<Self> {
+        // Tag values can't be empty in the line protocol.
+        if location.is_empty() {
+            anyhow::bail!("The location can't be empty when logging to InfluxDB");
+        }
+        let mut base_url =
+            reqwest::Url::parse(url).with_context(|| format!("Invalid InfluxDB URL {url}"))?;
+        // Keep any path prefix, such as for a reverse proxy, when joining.
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+        let mut write_url = base_url.join("api/v2/write")?;
+        write_url
+            .query_pairs_mut()
+            .append_pair("bucket", bucket)
+            .append_pair("precision", "ns");
+        if let Some(org) = org {
+            write_url.query_pairs_mut().append_pair("org", org);
+        }
+
+        let client = reqwest::Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .with_context(|| "Failed to create the HTTP client")?;
+
+        Ok(Self {
+            client,
+            write_url,
+            token,
+            location,
+            device_id,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl MeasurementSink for InfluxDbSink {
+    /// Points are identified by their tags and timestamp, so writing one
+    /// again overwrites it and `_id` isn't needed.
+    async fn write(
+        &self,
+        _id: Uuid,
+        measurement_time: &OffsetDateTime,
+        measurement: &Measurement,
+    ) -> anyhow::Result<()> {
+        let line = line_protocol(
+            &self.location,
+            &self.device_id,
+            measurement_time,
+            measurement,
+        );
+        let mut request = self.client.post(self.write_url.clone()).body(line);
+        if let Some(token) = &self.token {
+            request = request.header(reqwest::header::AUTHORIZATION, format!("Token {token}"));
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| "Failed to connect to InfluxDB")?;
+        if let Err(e) = response.error_for_status_ref() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(e).with_context(|| format!("InfluxDB rejected the write: {body}"));
+        }
+        Ok(())
+    }
+}
+
+/// Format a measurement as a line of the InfluxDB line protocol.
+fn line_protocol(
+    location: &str,
+    device_id: &str,
+    measurement_time: &OffsetDateTime,
+    measurement: &Measurement,
+) -> String {
+    let mut line = format!(
+        "{MEASUREMENT},location={},device_sn={} ",
+        escape_tag(location),
+        escape_tag(device_id)
+    );
+    let _ = write!(
+        line,
+        "temperature={},humidity={}",
+        f64::from(measurement.temperature().decidegrees_celsius()) / 10.0,
+        f64::from(measurement.humidity().per_mille()) / 10.0,
+    );
+    let integers = [
+        ("tvoc", measurement.tvoc),
+        ("eco2", measurement.eco2),
+        ("aqi", measurement.aqi.into()),
+        ("pm1_0", measurement.pm1_0),
+        ("pm2_5", measurement.pm2_5),
+        ("pm10", measurement.pm10),
+        ("pm1_0_in_air", measurement.pm1_0_in_air),
+        ("pm2_5_in_air", measurement.pm2_5_in_air),
+        ("pm10_in_air", measurement.pm10_in_air),
+        ("um0_3_particles", measurement.um_0_3_particles),
+        ("um0_5_particles", measurement.um_0_5_particles),
+        ("um1_particles", measurement.um_1_particles),
+        ("um2_5_particles", measurement.um_2_5_particles),
+        ("um5_particles", measurement.um_5_particles),
+        ("um10_particles", measurement.um_10_particles),
+    ];
+    for (field, value) in integers {
+        let _ = write!(line, ",{field}={value}i");
+    }
+    let _ = write!(line, " {}", measurement_time.unix_timestamp_nanos());
+    line
+}
+
+/// Escape the characters that are special in tag values.
+fn escape_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | '=' | ' ' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use apc1_mock::Simulator;
+
+    use super::*;
+
+    #[test]
+    fn format_line() {
+        let measurement = Simulator::new().readings;
+        let time = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+
+        let line = line_protocol("living room, upstairs", "42", &time, &measurement);
+
+        assert_eq!(
+            line,
+            concat!(
+                r"apc1,location=living\ room\,\ upstairs,device_sn=42 ",
+                "temperature=20.2,humidity=58.1,tvoc=37i,eco2=429i,aqi=1i,",
+                "pm1_0=0i,pm2_5=0i,pm10=0i,pm1_0_in_air=0i,pm2_5_in_air=0i,pm10_in_air=0i,",
+                "um0_3_particles=336i,um0_5_particles=100i,um1_particles=20i,",
+                "um2_5_particles=10i,um5_particles=0i,um10_particles=0i ",
+                "1700000000000000000"
+            )
+        );
+    }
+
+    #[test]
+    fn empty_location() {
+        let sink = |location: &str| {
+            InfluxDbSink::new(
+                "http://localhost:8086",
+                None,
+                "air",
+                None,
+                location.to_string(),
+                "42".to_string(),
+            )
+        };
+        assert!(sink("").is_err());
+        assert!(sink("office").is_ok());
+    }
+}
@@ -0,0 +1,220 @@
+//! Interactive first-time setup.
+use std::{
+    io::{BufRead, Write},
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use apc1_core::Measurement;
+
+use crate::{
+    config::{Config, InfluxDb, Sensor},
+    device::{self, Connection},
+    sink::SinkKind,
+    systemd,
+};
+
+/// The database suggested when the user has no preference.
+const DEFAULT_DB_URI: &str = "sqlite:///var/lib/apc1/readings.db";
+
+/// The InfluxDB server suggested when the user has no preference.
+const DEFAULT_INFLUXDB_URL: &str = "http://localhost:8086";
+
+/// Walk the user through creating a configuration file.
+///
+/// This looks for a sensor on every I2C bus, asks how the sensor is attached,
+/// where it is, and where measurements should go, and reads a measurement to
+/// confirm it works. It then writes the configuration and optionally a
+/// systemd service.
+pub async fn init(config_path: Option<PathBuf>) -> anyhow::Result<()> {
+    println!("Searching for an APC1 sensor...");
+    let buses = probe().await?;
+    for bus in &buses {
+        println!("Found a sensor on {}", bus.display());
+    }
+    if buses.is_empty() {
+        println!("No sensor found on an I2C bus; is the i2c-dev module loaded?");
+    }
+
+    let connection = match choose(
+        "How is the sensor attached (i2c, serial, or mock for a simulated sensor)",
+        &["i2c", "serial", "mock"],
+        "i2c",
+    )? {
+        "i2c" => {
+            let default = buses.first().map(|path| path.display().to_string());
+            Connection::I2cDevice(prompt("I2C device", default.as_deref())?.into())
+        }
+        "serial" => Connection::SerialDevice(prompt("Serial port", Some("/dev/ttyUSB0"))?.into()),
+        _ => Connection::Mock {},
+    };
+    let location = prompt("Where is the sensor located", None)?;
+    let interval = loop {
+        let interval = prompt("How often to record a measurement, in seconds", Some("60"))?;
+        match interval.parse::<NonZeroU64>() {
+            Ok(interval) => break interval,
+            Err(_) => println!("Please enter a whole number of seconds greater than 0"),
+        }
+    };
+
+    let (sink, db_uri, influxdb) = match choose(
+        "Where to send measurements (database or influxdb)",
+        &["database", "influxdb"],
+        "database",
+    )? {
+        "database" => {
+            let db_uri = prompt(
+                "Database to log measurements to (postgres:// or sqlite://)",
+                Some(DEFAULT_DB_URI),
+            )?;
+            (SinkKind::Database, Some(db_uri), None)
+        }
+        _ => {
+            let influxdb = InfluxDb {
+                url: prompt("InfluxDB server", Some(DEFAULT_INFLUXDB_URL))?,
+                org: Some(prompt("InfluxDB organization, if any", Some(""))?)
+                    .filter(|org| !org.is_empty()),
+                bucket: prompt("InfluxDB bucket", None)?,
+            };
+            println!("Set the APC1_INFLUXDB_TOKEN environment variable to the API token.");
+            (SinkKind::Influxdb, None, Some(influxdb))
+        }
+    };
+
+    println!("Taking a measurement to verify the sensor...");
+    let verified = verify(connection.clone()).await;
+    match &verified {
+        Ok(measurement) => println!("{measurement}"),
+        Err(e) => {
+            println!("Unable to read the sensor: {e:#}");
+            if !confirm("Save the configuration anyway?", false)? {
+                anyhow::bail!("Setup aborted");
+            }
+        }
+    }
+
+    if let Some(path) = db_uri
+        .as_deref()
+        .and_then(|uri| uri.strip_prefix("sqlite://"))
+    {
+        if let Some(parent) = Path::new(path).parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+    }
+    let config_path = match config_path {
+        Some(path) => path,
+        None => PathBuf::from(prompt(
+            "Where to save the configuration",
+            Some(crate::config::DEFAULT_PATH),
+        )?),
+    };
+    let config = Config {
+        i2c_device: None,
+        location: None,
+        interval: Some(interval),
+        db_uri,
+        sink: (sink != SinkKind::default()).then_some(sink),
+        influxdb,
+        sensors: vec![Sensor {
+            connection,
+            location,
+            interval: None,
+        }],
+        systemd: Default::default(),
+    };
+    config.save(&config_path)?;
+    println!("Wrote configuration to {}", config_path.display());
+
+    // A simulated sensor has no device for the service to use.
+    if verified.is_ok()
+        && !config.devices().is_empty()
+        && confirm(
+            &format!("Install a systemd service to {}?", systemd::SERVICE_PATH),
+            false,
+        )?
+    {
+        systemd::generate(&config_path, &config, true)?;
+    }
+    println!("Setup complete.");
+
+    Ok(())
+}
+
+/// Open the sensor and read a measurement from it, retrying while it starts.
+async fn verify(connection: Connection) -> anyhow::Result<Measurement> {
+    let mut dev = device::open(&connection).await?;
+    tokio::task::spawn_blocking(move || {
+        device::read_measurement_with_retry(&mut dev, &device::MEASUREMENT_RETRY)
+    })
+    .await?
+}
+
+/// Find every I2C bus with an APC1 attached, without writing to any of them.
+async fn probe() -> anyhow::Result<Vec<PathBuf>> {
+    let mut buses = std::fs::read_dir("/dev")
+        .with_context(|| "Failed to list /dev")?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("i2c-"))
+        })
+        .collect::<Vec<_>>();
+    buses.sort();
+
+    tokio::task::spawn_blocking(move || {
+        buses.retain(|bus| device::probe_i2c(bus));
+        buses
+    })
+    .await
+    .with_context(|| "Failed to probe the I2C buses")
+}
+
+/// Ask the user to pick one of `options`, returning the one they chose.
+fn choose<'a>(question: &str, options: &[&'a str], default: &str) -> anyhow::Result<&'a str> {
+    loop {
+        let answer = prompt(question, Some(default))?.to_lowercase();
+        match options.iter().find(|option| **option == answer) {
+            Some(option) => return Ok(option),
+            None => println!("Please enter one of: {}", options.join(", ")),
+        }
+    }
+}
+
+/// Ask the user a question, returning their answer or the default if they gave none.
+fn prompt(question: &str, default: Option<&str>) -> anyhow::Result<String> {
+    loop {
+        match default {
+            Some(default) if !default.is_empty() => print!("{question} [{default}]: "),
+            _ => print!("{question}: "),
+        }
+        std::io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if std::io::stdin().lock().read_line(&mut answer)? == 0 {
+            anyhow::bail!("Setup aborted");
+        }
+        match (answer.trim(), default) {
+            ("", Some(default)) => return Ok(default.to_string()),
+            ("", None) => continue,
+            (answer, _) => return Ok(answer.to_string()),
+        }
+    }
+}
+
+/// Ask the user a yes or no question.
+fn confirm(question: &str, default: bool) -> anyhow::Result<bool> {
+    let hint = if default { "Y/n" } else { "y/N" };
+    loop {
+        let answer = prompt(&format!("{question} ({hint})"), Some(""))?;
+        match answer.to_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => continue,
+        }
+    }
+}
@@ -1,230 +1,632 @@
-use std::{num::NonZeroU64, path::PathBuf};
+use std::{
+    net::SocketAddr,
+    num::{NonZeroU32, NonZeroU64},
+    path::PathBuf,
+};
 
 use anyhow::Context;
-use apc1_core::i2c;
-use apc1_core::Measurement;
+use apc1_core::Module;
 use clap::{Parser, Subcommand};
-use i2cdev::core::I2CDevice;
-use i2cdev::linux::LinuxI2CDevice;
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 use time::OffsetDateTime;
-use tokio::sync::mpsc::{self, Receiver};
+use uuid::Uuid;
 
-static MIGRATIONS: sqlx::migrate::Migrator = sqlx::migrate!("./migrations/");
+use crate::config::Config;
+use crate::device::{Connection, Device};
+use crate::influxdb::InfluxDbSink;
+use crate::sink::{DatabaseSink, MeasurementSink, SinkKind};
+use crate::storage::Database;
+
+mod config;
+mod daemon;
+mod device;
+mod diagnose;
+mod export;
+mod exporter;
+mod influxdb;
+mod init;
+mod migrate;
+mod mqtt;
+mod output;
+mod sink;
+mod storage;
+mod systemd;
+mod watch;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
     /// Full path to the i2c device provided by the i2c-dev kernel module.
     /// For example: /dev/i2c-1
+    ///
+    /// Required by all commands that talk to the device, unless a sensor is
+    /// set in the configuration file. Commands that read a single sensor use
+    /// the first one configured.
     #[arg(short, long)]
-    i2c_device: PathBuf,
+    i2c_device: Option<PathBuf>,
+    /// Full path to the serial port an APC1-U is attached to, in place of
+    /// --i2c-device. For example: /dev/ttyUSB0
+    #[arg(long, conflicts_with = "i2c_device")]
+    serial_device: Option<PathBuf>,
+    /// Use a simulated sensor in place of a real one, to try out commands
+    /// without the hardware.
+    #[arg(long, conflicts_with_all = ["i2c_device", "serial_device"])]
+    mock: bool,
+    /// Path to a configuration file, as written by the init command.
+    ///
+    /// Command-line arguments take precedence over the configuration file.
+    #[arg(short, long, env = "APC1_CONFIG")]
+    config: Option<PathBuf>,
     #[command(subcommand)]
     request: Request,
 }
 
 #[derive(Subcommand, Debug)]
 enum Request {
+    /// Find the sensor and interactively write a configuration file
+    Init,
     /// Show the module's name, serial number, and firmware version
     Module,
-    /// Read current air quality measurements from the device and print it to stdout
-    Measurement,
-    /// Log measurements to a PostgreSQL database
+    /// Read current air quality measurements from the device and print them to stdout
+    ///
+    /// A failed reading is retried a few times; if it keeps failing, the
+    /// command exits with an error.
+    Measurement {
+        /// How to print each measurement.
+        #[arg(long, value_enum, default_value_t)]
+        format: output::Format,
+        /// Stop after printing this many measurements, rather than running until interrupted.
+        #[arg(long)]
+        count: Option<u64>,
+        /// How frequently (in seconds) to print a measurement.
+        #[arg(long, default_value = "1")]
+        interval: NonZeroU64,
+    },
+    /// Check that the sensor is working, and print a health report
+    ///
+    /// An I2C sensor is reset first. The sensor fails if it reports a fault,
+    /// if more than 10% of its responses are corrupt, or if any value it
+    /// reports is outside the range in the datasheet; the command then exits
+    /// with an error.
+    Diagnose {
+        /// How many measurements to take.
+        #[arg(long, default_value = "10")]
+        samples: NonZeroU32,
+        /// How long (in seconds) to wait between measurements.
+        #[arg(long, default_value = "1")]
+        interval: NonZeroU64,
+        /// How to print the report.
+        #[arg(long, value_enum, default_value_t)]
+        format: diagnose::Format,
+    },
+    /// Show a continuously-updating dashboard of measurements in the terminal
+    Watch {
+        /// How frequently (in seconds) to read a measurement.
+        #[arg(long, default_value = "1")]
+        interval: NonZeroU64,
+        /// How many minutes of measurements to chart.
+        #[arg(long, default_value = "10")]
+        history: NonZeroU64,
+    },
+    /// Log measurements to a PostgreSQL or SQLite database, or to InfluxDB
     Log {
-        /// The database URI
+        /// The database URI, for example postgres://user@host/db or sqlite:///var/lib/apc1.db
         #[arg(env = "APC1_DB_URI")]
-        db_uri: String,
+        db_uri: Option<String>,
+        /// Where to write measurements. Defaults to the sink in the
+        /// configuration file, or a database.
+        #[arg(long, value_enum)]
+        sink: Option<SinkKind>,
+        /// The base URL of the InfluxDB server, for example http://localhost:8086
+        #[arg(long)]
+        influxdb_url: Option<String>,
+        /// The InfluxDB organization; not needed for InfluxDB 1.x.
+        #[arg(long)]
+        influxdb_org: Option<String>,
+        /// The InfluxDB bucket to write to.
+        #[arg(long)]
+        influxdb_bucket: Option<String>,
+        /// The InfluxDB API token.
+        #[arg(long, env = "APC1_INFLUXDB_TOKEN", hide_env_values = true)]
+        influxdb_token: Option<String>,
         /// How frequently (in seconds) to record a measurement.
         #[arg(long)]
-        interval: NonZeroU64,
+        interval: Option<NonZeroU64>,
         /// Identifies where the device is.
         #[arg(long)]
-        location: String,
+        location: Option<String>,
+        /// Record a single measurement and exit, rather than logging every interval.
+        #[arg(long)]
+        oneshot: bool,
+    },
+    /// Serve measurements as Prometheus metrics at /metrics
+    Exporter {
+        /// The address to serve metrics on.
+        #[arg(long, default_value = "0.0.0.0:9184")]
+        listen: SocketAddr,
+        /// How frequently (in seconds) to read a measurement. Defaults to the
+        /// sensor's interval in the configuration file, or 5.
+        #[arg(long)]
+        interval: Option<NonZeroU64>,
+        /// Identifies where the device is; added as a label to every metric.
+        #[arg(long)]
+        location: Option<String>,
+    },
+    /// Publish measurements to an MQTT broker, with Home Assistant discovery
+    PublishMqtt {
+        /// The hostname or address of the broker.
+        #[arg(long)]
+        host: String,
+        /// The port the broker listens on.
+        #[arg(long, default_value_t = 1883)]
+        port: u16,
+        #[arg(long, env = "APC1_MQTT_USERNAME")]
+        username: Option<String>,
+        #[arg(long, env = "APC1_MQTT_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+        /// The topic to publish measurements to. Defaults to apc1/<serial number>.
+        #[arg(long)]
+        topic: Option<String>,
+        /// The topic prefix Home Assistant uses for MQTT discovery.
+        #[arg(long, default_value = "homeassistant")]
+        discovery_prefix: String,
+        /// Don't publish Home Assistant discovery messages.
+        #[arg(long)]
+        no_discovery: bool,
+        /// How frequently (in seconds) to publish a measurement.
+        #[arg(long)]
+        interval: Option<NonZeroU64>,
+        /// Identifies where the device is.
+        #[arg(long)]
+        location: Option<String>,
+    },
+    /// Export logged measurements from a database as CSV or JSON
+    Export {
+        /// The database URI, for example postgres://user@host/db or sqlite:///var/lib/apc1.db
+        #[arg(env = "APC1_DB_URI")]
+        db_uri: Option<String>,
+        /// Only export measurements from this location.
+        #[arg(long)]
+        location: Option<String>,
+        /// Only export measurements taken at or after this RFC 3339 timestamp,
+        /// for example 2024-01-01T00:00:00Z.
+        #[arg(long, value_parser = parse_timestamp)]
+        start: Option<OffsetDateTime>,
+        /// Only export measurements taken before this RFC 3339 timestamp.
+        #[arg(long, value_parser = parse_timestamp)]
+        end: Option<OffsetDateTime>,
+        /// Average measurements from each device over windows of this length.
+        #[arg(long, value_enum)]
+        downsample: Option<export::Downsample>,
+        /// How to write the measurements.
+        #[arg(long, value_enum, default_value_t)]
+        format: export::Format,
+        /// Write to this file rather than stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Copy all logged measurements from one database to another
+    ///
+    /// Readings already present in the destination are skipped, so an
+    /// interrupted migration can be resumed by running it again.
+    MigrateData {
+        /// The URI of the database to copy readings from.
+        #[arg(long)]
+        from: String,
+        /// The URI of the database to copy readings to; it is created and
+        /// migrated as necessary.
+        #[arg(long)]
+        to: String,
+    },
+    /// Generate systemd units that run the logger with the configuration file
+    ///
+    /// Options given here override the [systemd] table of the configuration file.
+    Systemd {
+        /// Write the units to /etc/systemd/system rather than printing them.
+        #[arg(long)]
+        install: bool,
+        /// The user to run the service as.
+        #[arg(long)]
+        user: Option<String>,
+        /// A supplementary group that grants access to the I2C device, such as i2c.
+        #[arg(long)]
+        group: Option<String>,
+        /// An environment variable to set for the service, as KEY=VALUE.
+        /// May be given more than once.
+        #[arg(long = "env", value_parser = parse_key_value)]
+        environment: Vec<(String, String)>,
+        /// Restart the service if it has not read a measurement in this many seconds.
+        #[arg(long)]
+        watchdog_sec: Option<NonZeroU64>,
+        /// Record one measurement per run, started by a timer every interval.
+        #[arg(long)]
+        oneshot: bool,
     },
 }
 
-fn read_sensor(
-    mut dev: LinuxI2CDevice,
-    interval: u64,
-    dest: mpsc::Sender<(OffsetDateTime, Measurement)>,
-) -> anyhow::Result<()> {
-    let interval = std::time::Duration::from_secs(interval);
-    loop {
-        let mut buf: [u8; 64] = [0; 64];
-        dev.read(&mut buf)
-            .with_context(|| "Failed to read response into buffer")?;
-        match Measurement::try_from(&buf) {
-            Ok(measurement) => {
-                tracing::debug!("Read measurement successfully");
-                let measurement_time = OffsetDateTime::now_utc();
-                dest.blocking_send((measurement_time, measurement))?;
+fn parse_key_value(value: &str) -> anyhow::Result<(String, String)> {
+    let (key, value) = value
+        .split_once('=')
+        .with_context(|| "Expected KEY=VALUE")?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+fn parse_timestamp(value: &str) -> anyhow::Result<OffsetDateTime> {
+    OffsetDateTime::parse(value, &time::format_description::well_known::Rfc3339)
+        .with_context(|| "Expected an RFC 3339 timestamp, such as 2024-01-01T00:00:00Z")
+}
+
+/// Read the module ID, retrying for a few seconds while the device starts up.
+fn detect_module(dev: &mut Device) -> anyhow::Result<Module> {
+    device::read_module_with_retry(dev, &device::MODULE_RETRY)
+        .with_context(|| "Unable to detect module on the provided device.")
+}
+
+/// Open the sensor given on the command line or, failing that, the first in
+/// the configuration file.
+async fn open_device(
+    connection: Option<Connection>,
+    config: Option<&Config>,
+) -> anyhow::Result<Device> {
+    let connection = connection
+        .or_else(|| {
+            let config = config?;
+            config
+                .i2c_device
+                .clone()
+                .map(Connection::I2cDevice)
+                .or_else(|| Some(config.sensors.first()?.connection.clone()))
+        })
+        .with_context(|| "The --i2c-device or --serial-device argument is required")?;
+    device::open(&connection).await
+}
+
+/// Open every sensor to read from and identify its module.
+///
+/// A device given on the command line is used in place of those in the
+/// configuration file. Each sensor's interval is `interval` if given, then
+/// its interval in the configuration file, then `default_interval`.
+async fn open_sensors(
+    connection: Option<Connection>,
+    location: Option<String>,
+    interval: Option<NonZeroU64>,
+    default_interval: Option<NonZeroU64>,
+    config: Option<&Config>,
+) -> anyhow::Result<Vec<daemon::Sensor<Module>>> {
+    let connection = connection.or_else(|| config?.i2c_device.clone().map(Connection::I2cDevice));
+    let sensors = match connection {
+        Some(connection) => vec![config::Sensor {
+            connection,
+            location: location
+                .or_else(|| config?.location.clone())
+                .with_context(|| "The --location argument is required")?,
+            interval: None,
+        }],
+        None => {
+            let sensors = config
+                .map(|config| config.sensors.clone())
+                .unwrap_or_default();
+            if sensors.is_empty() {
+                anyhow::bail!("The --i2c-device or --serial-device argument is required");
             }
-            Err(e) => {
-                tracing::warn!(error=?e, "Measurement reading was invalid");
-                std::thread::sleep(std::time::Duration::from_millis(1100));
-                continue;
+            if location.is_some() {
+                anyhow::bail!("The --location argument can't be used with [[sensors]]");
             }
+            sensors
         }
-        std::thread::sleep(interval);
-    }
-}
+    };
 
-fn read_module(dev: &mut LinuxI2CDevice) -> anyhow::Result<apc1_core::Module> {
-    for (index, byte) in i2c::Command::ReadModuleId.to_bytes().iter().enumerate() {
-        dev.smbus_write_byte_data(i2c::COMMAND_BASE_ADDR + index as u8, *byte)
-            .with_context(|| "Failed to write the readmodule command")?;
+    let mut opened = vec![];
+    for sensor in sensors {
+        let interval = interval
+            .or(sensor.interval)
+            .or(default_interval)
+            .with_context(|| "The --interval argument is required")?;
+        let mut dev = device::open(&sensor.connection).await?;
+        let module = detect_module(&mut dev)
+            .with_context(|| format!("Failed to read {}", sensor.connection))?;
+        tracing::info!(
+            name = module.name_and_type.as_str(),
+            serial_number = module.serial_number,
+            location = sensor.location,
+            device = %sensor.connection,
+            "Detected APC1 sensor"
+        );
+        opened.push(daemon::Sensor {
+            location: sensor.location,
+            dev,
+            interval: std::time::Duration::from_secs(interval.into()),
+            context: module,
+        });
     }
-    let mut buf: [u8; 23] = [0; 23];
-    let base_addr = 0x47_u8;
-    for i in 0..23_u8 {
-        buf[i as usize] = dev
-            .smbus_read_byte_data(base_addr + i)
-            .with_context(|| "Failed to read response into buffer")?;
-    }
-    apc1_core::Module::try_from(&buf).with_context(|| "Response was invalid")
+
+    Ok(opened)
 }
 
 #[tokio::main]
 pub async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
-    let mut dev = LinuxI2CDevice::new(args.i2c_device, i2c::DEVICE_ADDR.into())
-        .with_context(|| "Unable to open the I2C device file. Is the i2c-dev module loaded?")?;
-
-    // Ensure the device is in a known state by requesting it reset.
-    for (index, byte) in i2c::Command::Reset.to_bytes().iter().enumerate() {
-        dev.smbus_write_byte_data(i2c::COMMAND_BASE_ADDR + index as u8, *byte)
-            .with_context(|| "Failed to write the readmodule command")?;
-    }
-    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    let config = match &args.config {
+        Some(path) if !matches!(args.request, Request::Init) => Some(Config::load(path)?),
+        _ => None,
+    };
+    let connection = match (args.i2c_device, args.serial_device) {
+        (Some(path), _) => Some(Connection::I2cDevice(path)),
+        (None, Some(path)) => Some(Connection::SerialDevice(path)),
+        (None, None) => args.mock.then_some(Connection::Mock {}),
+    };
 
     match args.request {
+        Request::Init => init::init(args.config).await?,
         Request::Module => {
-            let mut read_tries = 0;
-            loop {
-                if read_tries > 10 {
-                    anyhow::bail!("Unable to detect module on the provided I2C device.");
-                }
-                if let Ok(module) = read_module(&mut dev) {
-                    println!("{}", module);
-                    break;
+            let mut dev = open_device(connection, config.as_ref()).await?;
+            println!("{}", detect_module(&mut dev)?);
+        }
+        Request::Measurement {
+            format,
+            count,
+            interval,
+        } => {
+            let mut dev = open_device(connection, config.as_ref()).await?;
+            let serial_number = detect_module(&mut dev)?.serial_number;
+            let mut printer = output::Printer::new(format, std::io::stdout().lock());
+            let interval = std::time::Duration::from_secs(interval.into());
+            let mut printed = 0;
+            while count.is_none_or(|count| printed < count) {
+                let measurement =
+                    device::read_measurement_with_retry(&mut dev, &device::MEASUREMENT_RETRY)?;
+                printer.print(OffsetDateTime::now_utc(), serial_number, &measurement)?;
+                printed += 1;
+                if count.is_none_or(|count| printed < count) {
+                    std::thread::sleep(interval);
                 }
-                read_tries += 1;
-                std::thread::sleep(std::time::Duration::from_millis(250));
             }
         }
-        Request::Measurement => {
-            let mut buf: [u8; 64] = [0; 64];
-            for _ in 0..300 {
-                dev.read(&mut buf)
-                    .with_context(|| "Failed to read response into buffer")?;
-                if let Ok(measurement) = apc1_core::Measurement::try_from(&buf) {
-                    println!("{}", measurement);
-                }
-                std::thread::sleep(std::time::Duration::from_millis(1500));
+        Request::Diagnose {
+            samples,
+            interval,
+            format,
+        } => {
+            // Opening an I2C sensor resets it, so it's checked from its power-on state.
+            let mut dev = open_device(connection, config.as_ref()).await?;
+            let module = detect_module(&mut dev)?;
+            let reset = dev.was_reset();
+            let report = diagnose::diagnose(
+                &mut dev,
+                module,
+                reset,
+                samples.into(),
+                std::time::Duration::from_secs(interval.into()),
+            );
+            match format {
+                diagnose::Format::Text => print!("{report}"),
+                diagnose::Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
             }
+            if !report.passed {
+                anyhow::bail!("The sensor failed the health check");
+            }
+        }
+        Request::Watch { interval, history } => {
+            let mut dev = open_device(connection, config.as_ref()).await?;
+            let module = detect_module(&mut dev)?;
+            watch::watch(
+                dev,
+                module,
+                std::time::Duration::from_secs(interval.into()),
+                std::time::Duration::from_secs(u64::from(history) * 60),
+            )?;
         }
         Request::Log {
             db_uri,
+            sink,
+            influxdb_url,
+            influxdb_org,
+            influxdb_bucket,
+            influxdb_token,
             interval,
             location,
+            oneshot,
         } => {
             tracing_subscriber::fmt::init();
-            let pool = PgPoolOptions::new()
-                .max_connections(3)
-                .connect(&db_uri)
-                .await?;
-            MIGRATIONS.run(&pool).await?;
-
-            let device = loop {
-                match read_module(&mut dev) {
-                    Ok(module) => break module,
-                    Err(e) => {
-                        tracing::warn!(error=?e, "Failed to read I2C device; trying again...")
-                    }
+            // Connect before opening the devices so a bad database URI fails quickly.
+            let sink = sink.or_else(|| config.as_ref()?.sink).unwrap_or_default();
+            let (db, influxdb) = match sink {
+                SinkKind::Database => {
+                    let db_uri = db_uri
+                        .or_else(|| config.as_ref()?.db_uri.clone())
+                        .with_context(|| "A database URI is required")?;
+                    (Some(Database::connect(&db_uri).await?), None)
+                }
+                SinkKind::Influxdb => {
+                    let table = config.as_ref().and_then(|config| config.influxdb.as_ref());
+                    let influxdb = config::InfluxDb {
+                        url: influxdb_url
+                            .or_else(|| Some(table?.url.clone()))
+                            .with_context(|| "The --influxdb-url argument is required")?,
+                        org: influxdb_org.or_else(|| table?.org.clone()),
+                        bucket: influxdb_bucket
+                            .or_else(|| Some(table?.bucket.clone()))
+                            .with_context(|| "The --influxdb-bucket argument is required")?,
+                    };
+                    (None, Some(influxdb))
                 }
-                std::thread::sleep(std::time::Duration::from_millis(500));
             };
-            tracing::info!(
-                name = device.name_and_type,
-                serial_number = device.serial_number,
-                "Detected APC1 sensor"
-            );
+            let default_interval = config.as_ref().and_then(|config| config.interval);
+            let sensors = open_sensors(
+                connection,
+                location,
+                interval,
+                default_interval,
+                config.as_ref(),
+            )
+            .await?;
+
+            let mut readers = vec![];
+            for sensor in sensors {
+                let location = sensor.location.clone();
+                let device_id = sensor.context.serial_number.to_string();
+                let sink: Box<dyn MeasurementSink> = match (&db, &influxdb) {
+                    (Some(db), _) => Box::new(DatabaseSink::new(db.clone(), location, device_id)),
+                    (None, Some(influxdb)) => Box::new(InfluxDbSink::new(
+                        &influxdb.url,
+                        influxdb.org.as_deref(),
+                        &influxdb.bucket,
+                        influxdb_token.clone(),
+                        location,
+                        device_id,
+                    )?),
+                    (None, None) => unreachable!("every sink is configured above"),
+                };
+                readers.push(daemon::Sensor {
+                    location: sensor.location,
+                    dev: sensor.dev,
+                    interval: sensor.interval,
+                    context: sink,
+                });
+            }
 
-            let (sender, receiver) = mpsc::channel(64);
-            let db_writer = tokio::spawn(write_results(
+            if oneshot {
+                for mut reader in readers {
+                    let measurement = device::read_measurement_with_retry(
+                        &mut reader.dev,
+                        &device::MEASUREMENT_RETRY,
+                    )?;
+                    reader
+                        .context
+                        .write(Uuid::new_v4(), &OffsetDateTime::now_utc(), &measurement)
+                        .await?;
+                    tracing::info!(
+                        location = reader.location,
+                        "Logged measurement successfully"
+                    );
+                }
+                return Ok(());
+            }
+            daemon::run(readers, daemon::write_measurements).await?;
+        }
+        Request::Exporter {
+            listen,
+            interval,
+            location,
+        } => {
+            tracing_subscriber::fmt::init();
+            let default_interval = NonZeroU64::new(5);
+            let sensors = open_sensors(
+                connection,
                 location,
-                device.serial_number.to_string(),
-                pool,
-                receiver,
-            ));
-            let sensor_reader = tokio::task::spawn_blocking(move || {
-                read_sensor(dev, interval.into(), sender).unwrap();
-            });
-            let _result = tokio::join!(db_writer, sensor_reader);
+                interval,
+                default_interval,
+                config.as_ref(),
+            )
+            .await?;
+            let metrics = exporter::Metrics::new()?;
+            let sensors = sensors
+                .into_iter()
+                .map(|sensor| daemon::Sensor {
+                    context: metrics.sensor(&sensor.location, sensor.context.serial_number),
+                    location: sensor.location,
+                    dev: sensor.dev,
+                    interval: sensor.interval,
+                })
+                .collect();
+            exporter::run(sensors, listen, metrics).await?;
         }
-    }
-
-    Ok(())
-}
+        Request::PublishMqtt {
+            host,
+            port,
+            username,
+            password,
+            topic,
+            discovery_prefix,
+            no_discovery,
+            interval,
+            location,
+        } => {
+            tracing_subscriber::fmt::init();
+            let interval = interval
+                .or_else(|| config.as_ref()?.interval)
+                .with_context(|| "The --interval argument is required")?;
+            let location = location
+                .or_else(|| config.as_ref()?.location.clone())
+                .with_context(|| "The --location argument is required")?;
+            let mut dev = open_device(connection, config.as_ref()).await?;
+            let module = detect_module(&mut dev)?;
+            let settings = mqtt::Settings {
+                host,
+                port,
+                username,
+                password,
+                topic: topic.unwrap_or_else(|| format!("apc1/{}", module.serial_number)),
+                discovery_prefix: (!no_discovery).then_some(discovery_prefix),
+            };
 
-async fn write_results(
-    location: String,
-    device_id: String,
-    db: Pool<Postgres>,
-    mut receiver: Receiver<(OffsetDateTime, Measurement)>,
-) -> anyhow::Result<()> {
-    while let Some((measurement_time, measurement)) = receiver.recv().await {
-        if let Err(e) = sqlx::query!(
-                "
-                INSERT INTO apc_reading (
-                    measurement_time,
-                    location,
-                    device_sn,
-                    tvoc,
-                    eco2,
-                    aqi,
-                    temperature,
-                    humidity,
-                    pm1_0,
-                    pm2_5,
-                    pm10,
-                    pm1_0_in_air,
-                    pm2_5_in_air,
-                    pm10_in_air,
-                    um0_3_particles,
-                    um0_5_particles,
-                    um1_particles,
-                    um2_5_particles,
-                    um5_particles,
-                    um10_particles
-                )
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20)
-                ",
-                measurement_time,
-                &location,
-                &device_id,
-                measurement.tvoc as i32,
-                measurement.eco2 as i32,
-                measurement.aqi as i32,
-                measurement.t_comp as i32,
-                measurement.rh_comp as i32,
-                measurement.pm1_0 as i32,
-                measurement.pm2_5 as i32,
-                measurement.pm10 as i32,
-                measurement.pm1_0_in_air as i32,
-                measurement.pm2_5_in_air as i32,
-                measurement.pm10_in_air as i32,
-                measurement.um_0_3_particles as i32,
-                measurement.um_0_5_particles as i32,
-                measurement.um_1_particles as i32,
-                measurement.um_2_5_particles as i32,
-                measurement.um_5_particles as i32,
-                measurement.um_10_particles as i32,
-            ).execute(&db).await {
-                tracing::error!(error=?e, "Failed to write measurement to database");
-            } else {
-                tracing::info!(location, device_id, "Logged measurement successfully");
+            let sensor = daemon::Sensor {
+                location: location.clone(),
+                dev,
+                interval: std::time::Duration::from_secs(interval.into()),
+                context: (settings, location, module),
+            };
+            daemon::run(vec![sensor], |(settings, location, module), receiver| {
+                mqtt::publish(settings, location, module, receiver)
+            })
+            .await?;
+        }
+        Request::Export {
+            db_uri,
+            location,
+            start,
+            end,
+            downsample,
+            format,
+            output,
+        } => {
+            let db_uri = db_uri
+                .or_else(|| config.as_ref()?.db_uri.clone())
+                .with_context(|| "A database URI is required")?;
+            let db = Database::connect(&db_uri).await?;
+            let filter = export::Filter {
+                location: location.as_deref(),
+                start,
+                end,
+            };
+            match output {
+                Some(path) => {
+                    let file = std::fs::File::create(&path)
+                        .with_context(|| format!("Failed to create {}", path.display()))?;
+                    let file = std::io::BufWriter::new(file);
+                    export::export(&db, &filter, downsample, format, file).await?;
+                }
+                None => {
+                    let stdout = std::io::stdout().lock();
+                    export::export(&db, &filter, downsample, format, stdout).await?;
+                }
             }
+        }
+        Request::MigrateData { from, to } => {
+            tracing_subscriber::fmt::init();
+            let source = Database::connect(&from).await?;
+            let destination = Database::connect(&to).await?;
+            migrate::migrate_data(&source, &destination).await?;
+        }
+        Request::Systemd {
+            install,
+            user,
+            group,
+            environment,
+            watchdog_sec,
+            oneshot,
+        } => {
+            let (Some(config_path), Some(mut config)) = (args.config, config) else {
+                anyhow::bail!("The --config argument is required");
+            };
+            let settings = &mut config.systemd;
+            settings.user = user.or(settings.user.take());
+            settings.group = group.or(settings.group.take());
+            settings.environment.extend(environment);
+            settings.watchdog_sec = watchdog_sec.or(settings.watchdog_sec);
+            settings.oneshot |= oneshot;
+            systemd::generate(&config_path, &config, install)?;
+        }
     }
+
     Ok(())
 }
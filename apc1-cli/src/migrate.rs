@@ -0,0 +1,41 @@
+//! Copy logged readings from one database to another.
+use futures::TryStreamExt;
+
+use crate::storage::Database;
+
+/// How many readings to insert into the destination per statement.
+const BATCH_SIZE: usize = 500;
+
+/// Stream every reading in `source` into `destination`.
+///
+/// Readings are copied oldest first and keep their original UUID, so it is
+/// safe to re-run an interrupted migration; readings already present in the
+/// destination are skipped.
+pub async fn migrate_data(source: &Database, destination: &Database) -> anyhow::Result<()> {
+    let total = source.count_readings().await?;
+    tracing::info!(total, "Copying readings");
+
+    let mut readings = source.readings();
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut copied = 0_u64;
+    let mut inserted = 0_u64;
+    while let Some(reading) = readings.try_next().await? {
+        batch.push(reading);
+        if batch.len() == BATCH_SIZE {
+            inserted += destination.insert_readings(&batch).await?;
+            copied += batch.len() as u64;
+            batch.clear();
+            tracing::info!(copied, total, "Copied readings");
+        }
+    }
+    inserted += destination.insert_readings(&batch).await?;
+    copied += batch.len() as u64;
+
+    tracing::info!(
+        copied,
+        total,
+        already_present = copied - inserted,
+        "Finished copying readings"
+    );
+    Ok(())
+}
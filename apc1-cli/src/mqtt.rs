@@ -0,0 +1,263 @@
+//! Publish measurements to an MQTT broker, with Home Assistant discovery.
+//!
+//! Each measurement is published to the state topic as the same JSON object
+//! printed by `measurement --format json`. On every connection to the broker,
+//! a retained discovery message is published for each sensor entity so Home
+//! Assistant adds them automatically, and the availability topic is set to
+//! `online`. The broker sets it to `offline` if the connection is lost.
+use std::time::Duration;
+
+use apc1_core::{Measurement, Module};
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS};
+use serde_json::json;
+use time::OffsetDateTime;
+use tokio::sync::mpsc::Receiver;
+
+use crate::output::Record;
+
+/// How to connect to the broker and where to publish.
+pub struct Settings {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// The topic measurements are published to.
+    pub topic: String,
+    /// The prefix Home Assistant subscribes to for discovery, or `None` to
+    /// skip discovery.
+    pub discovery_prefix: Option<String>,
+}
+
+/// A Home Assistant sensor entity for one field of [`Record`].
+struct Entity {
+    /// The field of the JSON state holding the entity's value.
+    field: &'static str,
+    name: &'static str,
+    device_class: &'static str,
+    unit: Option<&'static str>,
+}
+
+const ENTITIES: [Entity; 9] = [
+    Entity {
+        field: "pm1_0",
+        name: "PM1.0",
+        device_class: "pm1",
+        unit: Some("µg/m³"),
+    },
+    Entity {
+        field: "pm2_5",
+        name: "PM2.5",
+        device_class: "pm25",
+        unit: Some("µg/m³"),
+    },
+    Entity {
+        field: "pm10",
+        name: "PM10",
+        device_class: "pm10",
+        unit: Some("µg/m³"),
+    },
+    Entity {
+        field: "tvoc",
+        name: "TVOC",
+        device_class: "volatile_organic_compounds_parts",
+        unit: Some("ppb"),
+    },
+    Entity {
+        field: "eco2",
+        name: "eCO2",
+        device_class: "carbon_dioxide",
+        unit: Some("ppm"),
+    },
+    Entity {
+        field: "temperature",
+        name: "Temperature",
+        device_class: "temperature",
+        unit: Some("°C"),
+    },
+    Entity {
+        field: "humidity",
+        name: "Humidity",
+        device_class: "humidity",
+        unit: Some("%"),
+    },
+    Entity {
+        field: "aqi",
+        name: "Air quality index",
+        device_class: "aqi",
+        unit: None,
+    },
+    Entity {
+        field: "epa_aqi",
+        name: "EPA air quality index",
+        device_class: "aqi",
+        unit: None,
+    },
+];
+
+/// The topic the broker publishes `online` or `offline` to.
+fn availability_topic(topic: &str) -> String {
+    format!("{topic}/availability")
+}
+
+/// The topic and payload of the discovery message for each entity.
+fn discovery_messages(
+    discovery_prefix: &str,
+    topic: &str,
+    location: &str,
+    module: &Module,
+) -> Vec<(String, String)> {
+    let node_id = format!("apc1_{}", module.serial_number);
+    let device = json!({
+        "identifiers": [node_id],
+        "name": format!("APC1 ({location})"),
+        "manufacturer": "ScioSense",
+        "model": module.name_and_type.as_str(),
+        "serial_number": module.serial_number.to_string(),
+        "sw_version": format!("{}.{}", module.fw_version_major, module.fw_version_minor),
+        "suggested_area": location,
+    });
+
+    ENTITIES
+        .iter()
+        .map(|entity| {
+            let mut config = json!({
+                "name": entity.name,
+                "unique_id": format!("{node_id}_{}", entity.field),
+                "state_topic": topic,
+                "availability_topic": availability_topic(topic),
+                "value_template": format!("{{{{ value_json.{} }}}}", entity.field),
+                "device_class": entity.device_class,
+                "state_class": "measurement",
+                "device": device,
+            });
+            if let Some(unit) = entity.unit {
+                config["unit_of_measurement"] = unit.into();
+            }
+            (
+                format!(
+                    "{discovery_prefix}/sensor/{node_id}/{}/config",
+                    entity.field
+                ),
+                config.to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Publish each measurement received until the sender is dropped.
+pub async fn publish(
+    settings: Settings,
+    location: String,
+    module: Module,
+    mut receiver: Receiver<(OffsetDateTime, Measurement)>,
+) -> anyhow::Result<()> {
+    let availability = availability_topic(&settings.topic);
+    let mut options = MqttOptions::new(
+        format!("apc1-{}", module.serial_number),
+        &settings.host,
+        settings.port,
+    );
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(
+        &availability,
+        "offline",
+        QoS::AtLeastOnce,
+        true,
+    ));
+    if let Some(username) = &settings.username {
+        options.set_credentials(username, settings.password.clone().unwrap_or_default());
+    }
+
+    // Discovery and availability are retained, so they only need to be sent
+    // when connecting, not with each measurement.
+    let mut on_connect = settings
+        .discovery_prefix
+        .as_deref()
+        .map(|prefix| discovery_messages(prefix, &settings.topic, &location, &module))
+        .unwrap_or_default();
+    on_connect.push((availability, "online".to_string()));
+
+    let (client, mut eventloop) = AsyncClient::new(options, 64);
+    let connection_client = client.clone();
+    let connection = tokio::spawn(async move {
+        loop {
+            match eventloop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    tracing::info!("Connected to the MQTT broker");
+                    for (topic, payload) in &on_connect {
+                        // The event loop must keep running to send these, so don't wait for room.
+                        if let Err(e) = connection_client.try_publish(
+                            topic,
+                            QoS::AtLeastOnce,
+                            true,
+                            payload.clone(),
+                        ) {
+                            tracing::error!(error=?e, topic, "Failed to queue message");
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!(error=?e, "MQTT connection failed; reconnecting...");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            }
+        }
+    });
+
+    // While the broker is down, queued messages fill the client's channel.
+    // Rather than wait for room, which would stall until it reconnects,
+    // measurements that don't fit are dropped.
+    let mut dropped: u64 = 0;
+    let result = async {
+        while let Some((measurement_time, measurement)) = receiver.recv().await {
+            let record = Record::new(measurement_time, module.serial_number, &measurement);
+            let payload = serde_json::to_string(&record)?;
+            match client.try_publish(&settings.topic, QoS::AtLeastOnce, false, payload) {
+                Ok(()) => tracing::info!(topic = settings.topic, "Queued measurement"),
+                Err(e) => {
+                    dropped += 1;
+                    tracing::warn!(error=?e, dropped, "Dropped measurement; the MQTT queue is full");
+                }
+            }
+        }
+        Ok(())
+    }
+    .await;
+    connection.abort();
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use apc1_mock::Simulator;
+
+    use super::*;
+
+    #[test]
+    fn discovery() {
+        let module = Simulator::new().module;
+
+        let messages = discovery_messages("homeassistant", "apc1/office", "office", &module);
+
+        assert_eq!(messages.len(), ENTITIES.len());
+        let (topic, payload) = &messages[1];
+        assert_eq!(
+            topic,
+            "homeassistant/sensor/apc1_607609401092424838/pm2_5/config"
+        );
+        let config: serde_json::Value = serde_json::from_str(payload).unwrap();
+        assert_eq!(config["unique_id"], "apc1_607609401092424838_pm2_5");
+        assert_eq!(config["state_topic"], "apc1/office");
+        assert_eq!(config["availability_topic"], "apc1/office/availability");
+        assert_eq!(config["value_template"], "{{ value_json.pm2_5 }}");
+        assert_eq!(config["unit_of_measurement"], "µg/m³");
+        assert_eq!(config["device"]["model"], "APC1-I");
+        assert_eq!(config["device"]["sw_version"], "0.35");
+
+        let config: serde_json::Value = serde_json::from_str(&messages[8].1).unwrap();
+        assert_eq!(config["value_template"], "{{ value_json.epa_aqi }}");
+        assert!(config.get("unit_of_measurement").is_none());
+    }
+}
@@ -0,0 +1,171 @@
+//! Print measurements in human or machine-readable formats.
+use std::io::Write;
+
+use apc1_core::{aqi::AirQualityIndex, Measurement};
+use clap::ValueEnum;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+/// How measurements are printed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// A block of text per measurement, intended to be read by people.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+    /// Comma-separated values with a header row.
+    Csv,
+}
+
+/// A measurement along with when and by which device it was taken, flattened for serialization.
+#[derive(Debug, Serialize)]
+pub struct Record {
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    pub serial_number: u64,
+    /// Degrees Celsius.
+    pub temperature: f32,
+    /// Percent relative humidity.
+    pub humidity: f32,
+    pub aqi: u8,
+    /// The US EPA AQI from the PM2.5 and PM10 concentrations, if they're valid.
+    pub epa_aqi: Option<u16>,
+    pub eco2: u16,
+    pub tvoc: u16,
+    pub pm1_0: u16,
+    pub pm2_5: u16,
+    pub pm10: u16,
+    pub pm1_0_in_air: u16,
+    pub pm2_5_in_air: u16,
+    pub pm10_in_air: u16,
+    pub um_0_3_particles: u16,
+    pub um_0_5_particles: u16,
+    pub um_1_particles: u16,
+    pub um_2_5_particles: u16,
+    pub um_5_particles: u16,
+    pub um_10_particles: u16,
+}
+
+impl Record {
+    pub fn new(timestamp: OffsetDateTime, serial_number: u64, measurement: &Measurement) -> Self {
+        Self {
+            timestamp,
+            serial_number,
+            temperature: measurement.temperature().celsius(),
+            humidity: measurement.humidity().percent(),
+            aqi: measurement.aqi,
+            epa_aqi: AirQualityIndex::from_measurement(measurement).map(|index| index.value),
+            eco2: measurement.eco2,
+            tvoc: measurement.tvoc,
+            pm1_0: measurement.pm1_0,
+            pm2_5: measurement.pm2_5,
+            pm10: measurement.pm10,
+            pm1_0_in_air: measurement.pm1_0_in_air,
+            pm2_5_in_air: measurement.pm2_5_in_air,
+            pm10_in_air: measurement.pm10_in_air,
+            um_0_3_particles: measurement.um_0_3_particles,
+            um_0_5_particles: measurement.um_0_5_particles,
+            um_1_particles: measurement.um_1_particles,
+            um_2_5_particles: measurement.um_2_5_particles,
+            um_5_particles: measurement.um_5_particles,
+            um_10_particles: measurement.um_10_particles,
+        }
+    }
+}
+
+/// Writes measurements to an output stream in a given format.
+///
+/// Each measurement is flushed as it's written so the output can be piped
+/// into other programs while measurements are still being taken.
+pub struct Printer<W: Write> {
+    format: Format,
+    output: W,
+    /// Whether the CSV header row has been written.
+    header_written: bool,
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(format: Format, output: W) -> Self {
+        Self {
+            format,
+            output,
+            header_written: false,
+        }
+    }
+
+    pub fn print(
+        &mut self,
+        timestamp: OffsetDateTime,
+        serial_number: u64,
+        measurement: &Measurement,
+    ) -> anyhow::Result<()> {
+        match self.format {
+            Format::Text => {
+                writeln!(
+                    self.output,
+                    "{} (serial number {serial_number})",
+                    timestamp.format(&time::format_description::well_known::Rfc3339)?
+                )?;
+                writeln!(self.output, "{measurement}")?;
+            }
+            Format::Json => {
+                serde_json::to_writer(
+                    &mut self.output,
+                    &Record::new(timestamp, serial_number, measurement),
+                )?;
+                writeln!(self.output)?;
+            }
+            Format::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(!self.header_written)
+                    .from_writer(&mut self.output);
+                writer.serialize(Record::new(timestamp, serial_number, measurement))?;
+                writer.flush()?;
+                self.header_written = true;
+            }
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use apc1_mock::Simulator;
+
+    use super::*;
+
+    fn print_twice(format: Format) -> String {
+        let measurement = Simulator::new().readings;
+        let mut printer = Printer::new(format, vec![]);
+        for _ in 0..2 {
+            printer
+                .print(OffsetDateTime::UNIX_EPOCH, 42, &measurement)
+                .unwrap();
+        }
+        String::from_utf8(printer.output).unwrap()
+    }
+
+    #[test]
+    fn json() {
+        let output = print_twice(Format::Json);
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        let record: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(record["timestamp"], "1970-01-01T00:00:00Z");
+        assert_eq!(record["serial_number"], 42);
+        assert_eq!(record["aqi"], 1);
+        assert_eq!(record["um_0_3_particles"], 336);
+    }
+
+    #[test]
+    fn csv_has_one_header() {
+        let output = print_twice(Format::Csv);
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("timestamp,serial_number,temperature,humidity,aqi,"));
+        assert!(lines[1].starts_with("1970-01-01T00:00:00Z,42,20.2,58.1,1,"));
+        assert_eq!(lines[1], lines[2]);
+    }
+}
@@ -0,0 +1,80 @@
+//! Destinations the logger can write measurements to.
+//!
+//! The sensor-reading loop only deals with [`MeasurementSink`], so adding a
+//! backend means implementing the trait and adding a [`SinkKind`] to select it.
+use apc1_core::Measurement;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::storage::Database;
+
+/// Somewhere measurements from a single sensor are recorded.
+///
+/// Sinks are created for a particular sensor, so they are responsible for
+/// labelling each measurement with its location and device.
+#[async_trait::async_trait]
+pub trait MeasurementSink: Send + Sync {
+    /// Record a measurement taken at the given time.
+    ///
+    /// `id` is the same each time a measurement is retried, so sinks which
+    /// can tell a write may have succeeded despite failing don't record it
+    /// twice.
+    async fn write(
+        &self,
+        id: Uuid,
+        measurement_time: &OffsetDateTime,
+        measurement: &Measurement,
+    ) -> anyhow::Result<()>;
+}
+
+/// The sinks that can be selected on the command line or in the
+/// configuration file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SinkKind {
+    /// A PostgreSQL or SQLite database.
+    #[default]
+    Database,
+    /// An InfluxDB bucket, written to with the line protocol.
+    Influxdb,
+}
+
+/// Writes measurements to the `apc_reading` table of a [`Database`].
+pub struct DatabaseSink {
+    db: Database,
+    location: String,
+    device_id: String,
+}
+
+impl DatabaseSink {
+    pub fn new(db: Database, location: String, device_id: String) -> Self {
+        Self {
+            db,
+            location,
+            device_id,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl MeasurementSink for DatabaseSink {
+    async fn write(
+        &self,
+        id: Uuid,
+        measurement_time: &OffsetDateTime,
+        measurement: &Measurement,
+    ) -> anyhow::Result<()> {
+        self.db
+            .log_measurement(
+                id,
+                *measurement_time,
+                &self.location,
+                &self.device_id,
+                measurement,
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+//! Database backends measurements can be logged to.
+//!
+//! Both backends share the same `apc_reading` table layout so readings can be
+//! moved between them without loss; see [`crate::migrate`].
+use std::str::FromStr;
+
+use anyhow::Context;
+use apc1_core::Measurement;
+use futures::stream::BoxStream;
+use sqlx::{
+    postgres::PgPoolOptions,
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    Pool, Postgres, QueryBuilder, Sqlite,
+};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+static POSTGRES_MIGRATIONS: sqlx::migrate::Migrator = sqlx::migrate!("./migrations/postgres/");
+static SQLITE_MIGRATIONS: sqlx::migrate::Migrator = sqlx::migrate!("./migrations/sqlite/");
+
+/// The columns of the `apc_reading` table, in the order [`Reading`] binds them.
+const READING_COLUMNS: &str = "uuid, created_on, measurement_time, location, device_sn, tvoc, \
+    eco2, aqi, temperature, humidity, pm1_0, pm2_5, pm10, pm1_0_in_air, pm2_5_in_air, \
+    pm10_in_air, um0_3_particles, um0_5_particles, um1_particles, um2_5_particles, \
+    um5_particles, um10_particles";
+
+/// A row of the `apc_reading` table.
+#[derive(Debug, sqlx::FromRow)]
+pub struct Reading {
+    pub uuid: Uuid,
+    pub created_on: OffsetDateTime,
+    pub measurement_time: OffsetDateTime,
+    pub location: String,
+    pub device_sn: String,
+    pub tvoc: i32,
+    pub eco2: i32,
+    pub aqi: i32,
+    pub temperature: i32,
+    pub humidity: i32,
+    pub pm1_0: i32,
+    pub pm2_5: i32,
+    pub pm10: i32,
+    pub pm1_0_in_air: i32,
+    pub pm2_5_in_air: i32,
+    pub pm10_in_air: i32,
+    pub um0_3_particles: i32,
+    pub um0_5_particles: i32,
+    pub um1_particles: i32,
+    pub um2_5_particles: i32,
+    pub um5_particles: i32,
+    pub um10_particles: i32,
+}
+
+impl Reading {
+    pub fn new(
+        uuid: Uuid,
+        measurement_time: OffsetDateTime,
+        location: &str,
+        device_sn: &str,
+        measurement: &Measurement,
+    ) -> Self {
+        Self {
+            uuid,
+            created_on: OffsetDateTime::now_utc(),
+            measurement_time,
+            location: location.to_string(),
+            device_sn: device_sn.to_string(),
+            tvoc: measurement.tvoc.into(),
+            eco2: measurement.eco2.into(),
+            aqi: measurement.aqi.into(),
+            temperature: measurement.t_comp.into(),
+            humidity: measurement.rh_comp.into(),
+            pm1_0: measurement.pm1_0.into(),
+            pm2_5: measurement.pm2_5.into(),
+            pm10: measurement.pm10.into(),
+            pm1_0_in_air: measurement.pm1_0_in_air.into(),
+            pm2_5_in_air: measurement.pm2_5_in_air.into(),
+            pm10_in_air: measurement.pm10_in_air.into(),
+            um0_3_particles: measurement.um_0_3_particles.into(),
+            um0_5_particles: measurement.um_0_5_particles.into(),
+            um1_particles: measurement.um_1_particles.into(),
+            um2_5_particles: measurement.um_2_5_particles.into(),
+            um5_particles: measurement.um_5_particles.into(),
+            um10_particles: measurement.um_10_particles.into(),
+        }
+    }
+}
+
+/// A connection pool to one of the supported databases.
+#[derive(Clone)]
+pub enum Database {
+    Postgres(Pool<Postgres>),
+    Sqlite(Pool<Sqlite>),
+}
+
+impl Database {
+    /// Connect to the database at the given URI and apply any pending migrations.
+    ///
+    /// The backend is selected by the URI scheme, either `postgres://` (or
+    /// `postgresql://`) or `sqlite://`. SQLite databases are created if they
+    /// do not exist.
+    pub async fn connect(uri: &str) -> anyhow::Result<Self> {
+        let database = match uri.split_once("://").map(|(scheme, _)| scheme) {
+            Some("postgres" | "postgresql") => {
+                let pool = PgPoolOptions::new()
+                    .max_connections(3)
+                    .connect(uri)
+                    .await
+                    .with_context(|| "Failed to connect to the PostgreSQL database")?;
+                POSTGRES_MIGRATIONS.run(&pool).await?;
+                Self::Postgres(pool)
+            }
+            Some("sqlite") => {
+                let options = SqliteConnectOptions::from_str(uri)?.create_if_missing(true);
+                let pool = SqlitePoolOptions::new()
+                    .max_connections(1)
+                    .connect_with(options)
+                    .await
+                    .with_context(|| "Failed to open the SQLite database")?;
+                SQLITE_MIGRATIONS.run(&pool).await?;
+                Self::Sqlite(pool)
+            }
+            _ => anyhow::bail!(
+                "Unsupported database URI; expected one starting with postgres:// or sqlite://"
+            ),
+        };
+
+        Ok(database)
+    }
+
+    /// Record a measurement taken by the device with the given serial number.
+    ///
+    /// `uuid` identifies the reading, so logging it again, as when retrying a
+    /// write that may have succeeded, doesn't add a second row.
+    pub async fn log_measurement(
+        &self,
+        uuid: Uuid,
+        measurement_time: OffsetDateTime,
+        location: &str,
+        device_id: &str,
+        measurement: &Measurement,
+    ) -> Result<(), sqlx::Error> {
+        let reading = Reading::new(uuid, measurement_time, location, device_id, measurement);
+        self.insert_readings(&[reading]).await?;
+        Ok(())
+    }
+
+    /// The total number of readings stored.
+    pub async fn count_readings(&self) -> Result<i64, sqlx::Error> {
+        let query = "SELECT COUNT(*) FROM apc_reading";
+        match self {
+            Self::Postgres(db) => sqlx::query_scalar(query).fetch_one(db).await,
+            Self::Sqlite(db) => sqlx::query_scalar(query).fetch_one(db).await,
+        }
+    }
+
+    /// Stream every stored reading, oldest first.
+    pub fn readings(&self) -> BoxStream<'_, Result<Reading, sqlx::Error>> {
+        let query = "SELECT * FROM apc_reading ORDER BY measurement_time, uuid";
+        match self {
+            Self::Postgres(db) => sqlx::query_as(query).fetch(db),
+            Self::Sqlite(db) => sqlx::query_as(query).fetch(db),
+        }
+    }
+
+    /// Stream the readings taken at a location, or anywhere if `location` is
+    /// `None`, from `start` (inclusive) until `end` (exclusive), oldest first.
+    pub fn readings_between<'a>(
+        &'a self,
+        location: Option<&'a str>,
+        start: Option<OffsetDateTime>,
+        end: Option<OffsetDateTime>,
+    ) -> BoxStream<'a, Result<Reading, sqlx::Error>> {
+        let query = "SELECT * FROM apc_reading \
+            WHERE ($1 IS NULL OR location = $1) \
+            AND ($2 IS NULL OR measurement_time >= $2) \
+            AND ($3 IS NULL OR measurement_time < $3) \
+            ORDER BY measurement_time, uuid";
+        // SQLite stores times as text, which only sorts correctly in a single offset.
+        let start = start.map(|start| start.to_offset(time::UtcOffset::UTC));
+        let end = end.map(|end| end.to_offset(time::UtcOffset::UTC));
+        match self {
+            Self::Postgres(db) => sqlx::query_as(query)
+                .bind(location)
+                .bind(start)
+                .bind(end)
+                .fetch(db),
+            Self::Sqlite(db) => sqlx::query_as(query)
+                .bind(location)
+                .bind(start)
+                .bind(end)
+                .fetch(db),
+        }
+    }
+
+    /// Insert a batch of readings with a single statement.
+    ///
+    /// Readings whose UUID is already present are skipped, so a partially
+    /// completed copy can safely be run again. Returns the number of rows
+    /// that were inserted.
+    pub async fn insert_readings(&self, readings: &[Reading]) -> Result<u64, sqlx::Error> {
+        if readings.is_empty() {
+            return Ok(0);
+        }
+
+        let rows_affected = match self {
+            Self::Postgres(db) => insert_query::<Postgres>(readings)
+                .build()
+                .execute(db)
+                .await?
+                .rows_affected(),
+            Self::Sqlite(db) => insert_query::<Sqlite>(readings)
+                .build()
+                .execute(db)
+                .await?
+                .rows_affected(),
+        };
+        Ok(rows_affected)
+    }
+}
+
+/// Build an insert of `readings` which skips any whose UUID is already in the table.
+///
+/// Both backends share it so their inserts can't drift apart.
+fn insert_query<'a, DB>(readings: &'a [Reading]) -> QueryBuilder<'a, DB>
+where
+    DB: sqlx::Database,
+    DB::Arguments<'a>: Default,
+    Uuid: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    OffsetDateTime: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    String: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+    i32: sqlx::Encode<'a, DB> + sqlx::Type<DB>,
+{
+    let mut builder = QueryBuilder::new(format!("INSERT INTO apc_reading ({READING_COLUMNS}) "));
+    builder.push_values(readings, |mut row, reading| {
+        row.push_bind(reading.uuid)
+            .push_bind(reading.created_on)
+            .push_bind(reading.measurement_time)
+            .push_bind(&reading.location)
+            .push_bind(&reading.device_sn)
+            .push_bind(reading.tvoc)
+            .push_bind(reading.eco2)
+            .push_bind(reading.aqi)
+            .push_bind(reading.temperature)
+            .push_bind(reading.humidity)
+            .push_bind(reading.pm1_0)
+            .push_bind(reading.pm2_5)
+            .push_bind(reading.pm10)
+            .push_bind(reading.pm1_0_in_air)
+            .push_bind(reading.pm2_5_in_air)
+            .push_bind(reading.pm10_in_air)
+            .push_bind(reading.um0_3_particles)
+            .push_bind(reading.um0_5_particles)
+            .push_bind(reading.um1_particles)
+            .push_bind(reading.um2_5_particles)
+            .push_bind(reading.um5_particles)
+            .push_bind(reading.um10_particles);
+    });
+    builder.push(" ON CONFLICT (uuid) DO NOTHING");
+    builder
+}
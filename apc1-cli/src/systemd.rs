@@ -0,0 +1,321 @@
+//! Generate systemd units to run the logger as a service.
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, sink::SinkKind};
+
+/// Where the service unit is installed.
+pub const SERVICE_PATH: &str = "/etc/systemd/system/apc1.service";
+
+/// Where the timer unit is installed when logging in oneshot mode.
+pub const TIMER_PATH: &str = "/etc/systemd/system/apc1.timer";
+
+/// Settings for the generated units, from the `[systemd]` table of the configuration file.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// The user to run the service as; root if unset.
+    pub user: Option<String>,
+    /// Supplementary group granting access to the I2C device, often `i2c`.
+    pub group: Option<String>,
+    /// Environment variables to set for the service, for example `RUST_LOG`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub environment: BTreeMap<String, String>,
+    /// Restart the service if it has not read a measurement in this many
+    /// seconds. Must be longer than the logging interval.
+    pub watchdog_sec: Option<NonZeroU64>,
+    /// Take a single measurement per run and schedule runs with a timer,
+    /// rather than keeping the service running.
+    pub oneshot: bool,
+}
+
+impl Settings {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Everything needed to render the units.
+pub struct Unit<'a> {
+    /// The `apc1` executable to run.
+    pub executable: &'a Path,
+    /// The configuration file to pass to the executable.
+    pub config: &'a Path,
+    /// The I2C and serial devices the service needs access to.
+    pub devices: Vec<&'a Path>,
+    /// The database measurements are logged to, unless they go elsewhere.
+    pub db_uri: Option<&'a str>,
+    /// How frequently (in seconds) the timer starts the service in oneshot
+    /// mode.
+    pub interval: NonZeroU64,
+    pub settings: &'a Settings,
+}
+
+impl Unit<'_> {
+    /// Render the service unit.
+    ///
+    /// The service runs with most of the filesystem read-only and only the
+    /// I2C device available, so it can write nowhere but `/var/lib/apc1` and,
+    /// for SQLite, the directory holding the database.
+    pub fn render_service(&self) -> String {
+        let mut unit = String::new();
+        let _ = write!(
+            unit,
+            concat!(
+                "[Unit]\n",
+                "Description=Log air quality measurements from an APC1 sensor\n",
+                "Wants=network-online.target\n",
+                "After=network-online.target\n",
+                "\n",
+                "[Service]\n",
+            ),
+        );
+
+        let mut exec_start = format!(
+            "{} --config {} log",
+            self.executable.display(),
+            self.config.display()
+        );
+        if self.settings.oneshot {
+            exec_start.push_str(" --oneshot");
+            let _ = writeln!(unit, "Type=oneshot");
+        } else if let Some(watchdog_sec) = self.settings.watchdog_sec {
+            let _ = writeln!(unit, "Type=notify");
+            let _ = writeln!(unit, "NotifyAccess=main");
+            let _ = writeln!(unit, "WatchdogSec={watchdog_sec}");
+            let _ = writeln!(unit, "Restart=on-failure");
+            let _ = writeln!(unit, "RestartSec=10");
+        } else {
+            let _ = writeln!(unit, "Type=exec");
+            let _ = writeln!(unit, "Restart=on-failure");
+            let _ = writeln!(unit, "RestartSec=10");
+        }
+        let _ = writeln!(unit, "ExecStart={exec_start}");
+
+        if let Some(user) = &self.settings.user {
+            let _ = writeln!(unit, "User={user}");
+        }
+        if let Some(group) = &self.settings.group {
+            let _ = writeln!(unit, "SupplementaryGroups={group}");
+        }
+        for (key, value) in &self.settings.environment {
+            let _ = writeln!(unit, "Environment=\"{key}={}\"", escape(value));
+        }
+
+        let _ = write!(
+            unit,
+            concat!("\n", "StateDirectory=apc1\n", "DevicePolicy=closed\n",),
+        );
+        for device in &self.devices {
+            let _ = writeln!(unit, "DeviceAllow={} rw", device.display());
+        }
+        let _ = write!(
+            unit,
+            concat!(
+                "NoNewPrivileges=yes\n",
+                "ProtectSystem=strict\n",
+                "ProtectHome=yes\n",
+                "PrivateTmp=yes\n",
+                "ProtectKernelTunables=yes\n",
+                "ProtectKernelModules=yes\n",
+                "ProtectKernelLogs=yes\n",
+                "ProtectControlGroups=yes\n",
+                "ProtectClock=yes\n",
+                "ProtectHostname=yes\n",
+                "RestrictAddressFamilies=AF_UNIX AF_INET AF_INET6\n",
+                "RestrictNamespaces=yes\n",
+                "RestrictRealtime=yes\n",
+                "RestrictSUIDSGID=yes\n",
+                "LockPersonality=yes\n",
+                "MemoryDenyWriteExecute=yes\n",
+                "SystemCallArchitectures=native\n",
+                "SystemCallFilter=@system-service\n",
+                "CapabilityBoundingSet=\n",
+            ),
+        );
+        if let Some(directory) = self.sqlite_directory() {
+            let _ = writeln!(unit, "ReadWritePaths={}", directory.display());
+        }
+
+        if !self.settings.oneshot {
+            let _ = write!(
+                unit,
+                concat!("\n", "[Install]\n", "WantedBy=multi-user.target\n")
+            );
+        }
+
+        unit
+    }
+
+    /// Render the timer unit which starts the service every interval in oneshot mode.
+    pub fn render_timer(&self) -> String {
+        format!(
+            concat!(
+                "[Unit]\n",
+                "Description=Periodically log an air quality measurement from an APC1 sensor\n",
+                "\n",
+                "[Timer]\n",
+                "OnBootSec={interval}\n",
+                "OnUnitActiveSec={interval}\n",
+                "AccuracySec=1\n",
+                "\n",
+                "[Install]\n",
+                "WantedBy=timers.target\n",
+            ),
+            interval = self.interval,
+        )
+    }
+
+    /// The directory containing the SQLite database, if one is used outside the state directory.
+    fn sqlite_directory(&self) -> Option<PathBuf> {
+        let path = self.db_uri?.strip_prefix("sqlite://")?;
+        let path = path.split_once('?').map_or(path, |(path, _)| path);
+        let directory = Path::new(path).parent()?;
+        if directory.is_absolute() && !directory.starts_with("/var/lib/apc1") {
+            Some(directory.to_path_buf())
+        } else {
+            None
+        }
+    }
+}
+
+/// Escape a value for a double-quoted assignment in `Environment=`.
+///
+/// `%` starts a specifier, and backslashes, quotes and control characters
+/// would otherwise end the value or the line.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '%' => escaped.push_str("%%"),
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\x{:02x}", u32::from(c));
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Whether `key` is a name systemd accepts for an environment variable.
+fn is_valid_variable(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Render the units for the given configuration file and either print or install them.
+pub fn generate(config_path: &Path, config: &Config, install: bool) -> anyhow::Result<()> {
+    let settings = &config.systemd;
+    if let Some(key) = settings
+        .environment
+        .keys()
+        .find(|key| !is_valid_variable(key))
+    {
+        anyhow::bail!("{key:?} is not a valid environment variable name");
+    }
+    let devices = config.devices();
+    if devices.is_empty() {
+        anyhow::bail!("The configuration must set i2c_device or list [[sensors]]");
+    }
+    let db_uri = config.db_uri.as_deref();
+    if config.sink.unwrap_or_default() == SinkKind::Database && db_uri.is_none() {
+        anyhow::bail!("The configuration must set db_uri for the service to log to");
+    }
+    let intervals = config.intervals().with_context(|| {
+        "The configuration must set interval, either at the top level or for every sensor"
+    })?;
+    if let Some(watchdog_sec) = settings.watchdog_sec {
+        let interval = intervals.iter().max().copied().unwrap_or(NonZeroU64::MIN);
+        if !settings.oneshot && watchdog_sec <= interval {
+            anyhow::bail!(
+                "The watchdog timeout ({watchdog_sec}s) must be longer than the logging interval ({interval}s)",
+            );
+        }
+    }
+
+    let executable = std::env::current_exe()
+        .with_context(|| "Unable to determine the path to this executable")?;
+    let config_path = std::path::absolute(config_path)?;
+    let unit = Unit {
+        executable: &executable,
+        config: &config_path,
+        devices,
+        db_uri,
+        // Each run reads every sensor, so runs are as frequent as the most
+        // frequently read sensor.
+        interval: intervals.iter().min().copied().unwrap_or(NonZeroU64::MIN),
+        settings,
+    };
+
+    let service = unit.render_service();
+    let timer = settings.oneshot.then(|| unit.render_timer());
+    if install {
+        std::fs::write(SERVICE_PATH, service)
+            .with_context(|| format!("Failed to write {SERVICE_PATH}"))?;
+        println!("Installed {SERVICE_PATH}");
+        let enable = if let Some(timer) = timer {
+            std::fs::write(TIMER_PATH, timer)
+                .with_context(|| format!("Failed to write {TIMER_PATH}"))?;
+            println!("Installed {TIMER_PATH}");
+            "apc1.timer"
+        } else {
+            "apc1.service"
+        };
+        println!("Start it with `systemctl daemon-reload && systemctl enable --now {enable}`");
+    } else {
+        println!("# {SERVICE_PATH}\n{service}");
+        if let Some(timer) = timer {
+            println!("# {TIMER_PATH}\n{timer}");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn environment_is_escaped() {
+        let settings = Settings {
+            environment: BTreeMap::from([(
+                "APC1_DB_URI".to_string(),
+                "postgres://apc1:50%\"\\@db/apc1\nExecStartPre=/bin/sh".to_string(),
+            )]),
+            ..Default::default()
+        };
+        let unit = Unit {
+            executable: Path::new("/usr/bin/apc1"),
+            config: Path::new("/etc/apc1/config.toml"),
+            devices: vec![Path::new("/dev/i2c-1")],
+            db_uri: Some("sqlite:///var/lib/apc1/readings.db"),
+            interval: NonZeroU64::MIN,
+            settings: &settings,
+        };
+
+        let service = unit.render_service();
+
+        assert!(service.lines().any(|line| line
+            == r#"Environment="APC1_DB_URI=postgres://apc1:50%%\"\\@db/apc1\nExecStartPre=/bin/sh""#));
+        assert!(!service.lines().any(|line| line.starts_with("ExecStartPre")));
+        assert!(is_valid_variable("RUST_LOG"));
+        assert!(!is_valid_variable("RUST LOG"));
+        assert!(!is_valid_variable("1RUST_LOG"));
+        assert!(!is_valid_variable(""));
+    }
+}
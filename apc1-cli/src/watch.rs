@@ -0,0 +1,291 @@
+//! A live dashboard of measurements in the terminal.
+use std::{
+    collections::VecDeque,
+    sync::mpsc::{self, Receiver, Sender},
+    time::Duration,
+};
+
+use apc1_core::{aqi::AirQualityIndex, DeviceErrorCode, DeviceFault, Measurement, Module};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::Line,
+    widgets::{Block, Paragraph, Row, Sparkline, Table},
+    Frame,
+};
+use time::OffsetDateTime;
+
+use crate::device::{self, Device};
+
+/// How long to wait for a key press before checking for new measurements.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The name of each value charted in the history panel and how to get it.
+type Chart = (&'static str, fn(&Measurement) -> u64);
+
+const CHARTS: [Chart; 4] = [
+    ("PM2.5", |m| m.pm2_5.into()),
+    ("PM10", |m| m.pm10.into()),
+    ("TVOC", |m| m.tvoc.into()),
+    ("eCO2", |m| m.eco2.into()),
+];
+
+/// What the reader thread reports to the dashboard.
+enum Update {
+    Measurement(OffsetDateTime, Measurement),
+    Error(anyhow::Error),
+}
+
+/// The dashboard's state.
+struct Dashboard {
+    module: Module,
+    /// How far back the sparklines go.
+    history_window: time::Duration,
+    /// Measurements within the history window, oldest first.
+    history: VecDeque<(OffsetDateTime, Measurement)>,
+    /// The faults in the most recent reading, which are only known when the
+    /// device refuses to send a measurement.
+    faults: DeviceErrorCode,
+    read_errors: u64,
+    last_error: Option<String>,
+}
+
+impl Dashboard {
+    fn new(module: Module, history_window: time::Duration) -> Self {
+        Self {
+            module,
+            history_window,
+            history: VecDeque::new(),
+            faults: DeviceErrorCode::from_bits(0),
+            read_errors: 0,
+            last_error: None,
+        }
+    }
+
+    fn update(&mut self, update: Update) {
+        match update {
+            Update::Measurement(measurement_time, measurement) => {
+                self.faults = DeviceErrorCode::from_bits(0);
+                self.history.push_back((measurement_time, measurement));
+                while self
+                    .history
+                    .front()
+                    .is_some_and(|(oldest, _)| measurement_time - *oldest > self.history_window)
+                {
+                    self.history.pop_front();
+                }
+            }
+            Update::Error(e) => {
+                if let Some(apc1_core::Error::Device(code)) = device::response_error(&e) {
+                    self.faults = *code;
+                }
+                self.read_errors += 1;
+                self.last_error = Some(format!("{e:#}"));
+            }
+        }
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [status, body] =
+            Layout::vertical([Constraint::Length(3), Constraint::Fill(1)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Length(46), Constraint::Fill(1)]).areas(body);
+        let [current, faults] =
+            Layout::vertical([Constraint::Length(11), Constraint::Fill(1)]).areas(left);
+
+        self.render_status(frame, status);
+        self.render_current(frame, current);
+        self.render_faults(frame, faults);
+        self.render_history(frame, right);
+    }
+
+    fn render_status(&self, frame: &mut Frame, area: Rect) {
+        let updated = match self.history.back() {
+            Some((measurement_time, _)) => measurement_time
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_default(),
+            None => "waiting for a measurement".to_string(),
+        };
+        let mut line = vec![
+            format!(
+                "{} (serial number {})",
+                self.module.name_and_type, self.module.serial_number
+            )
+            .bold(),
+            format!("  Updated: {updated}  Read errors: {}", self.read_errors).into(),
+        ];
+        if let Some(error) = &self.last_error {
+            line.push(format!("  Last error: {error}").dark_gray());
+        }
+        let paragraph = Paragraph::new(Line::from(line))
+            .block(Block::bordered().title(" apc1 watch (q to quit) "));
+        frame.render_widget(paragraph, area);
+    }
+
+    fn render_current(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" Current ");
+        let Some((_, measurement)) = self.history.back() else {
+            frame.render_widget(Paragraph::new("No measurements yet").block(block), area);
+            return;
+        };
+        let rows = [
+            ("PM1.0", format!("{} µg/m³", measurement.pm1_0)),
+            ("PM2.5", format!("{} µg/m³", measurement.pm2_5)),
+            ("PM10", format!("{} µg/m³", measurement.pm10)),
+            ("TVOC", format!("{} ppb", measurement.tvoc)),
+            ("eCO2", format!("{} ppm", measurement.eco2)),
+            ("AQI", measurement.aqi.to_string()),
+            (
+                "EPA AQI",
+                AirQualityIndex::from_measurement(measurement)
+                    .map_or("out of range".to_string(), |index| index.to_string()),
+            ),
+            ("Temperature", measurement.temperature().to_string()),
+            ("Humidity", measurement.humidity().to_string()),
+        ]
+        .map(|(name, value)| Row::new([name.to_string(), value]));
+        let table = Table::new(rows, [Constraint::Length(12), Constraint::Fill(1)]).block(block);
+        frame.render_widget(table, area);
+    }
+
+    fn render_faults(&self, frame: &mut Frame, area: Rect) {
+        let rows = DeviceFault::ALL.map(|fault| {
+            let (state, color) = if self.faults.contains(fault) {
+                ("FAULT", Color::Red)
+            } else {
+                ("ok", Color::Green)
+            };
+            Row::new([fault.to_string(), state.to_string()]).style(Style::new().fg(color))
+        });
+        let table = Table::new(rows, [Constraint::Fill(1), Constraint::Length(5)])
+            .block(Block::bordered().title(" Device faults "));
+        frame.render_widget(table, area);
+    }
+
+    fn render_history(&self, frame: &mut Frame, area: Rect) {
+        let areas = Layout::vertical([Constraint::Fill(1); CHARTS.len()]).split(area);
+        for ((name, value), area) in CHARTS.into_iter().zip(areas.iter()) {
+            // Show the most recent measurements that fit in the chart.
+            let width = area.width.saturating_sub(2) as usize;
+            let data = self
+                .history
+                .iter()
+                .skip(self.history.len().saturating_sub(width))
+                .map(|(_, measurement)| value(measurement))
+                .collect::<Vec<_>>();
+            let minutes = self.history_window.whole_minutes();
+            let sparkline = Sparkline::default()
+                .block(Block::bordered().title(format!(" {name}, last {minutes} minutes ")))
+                .data(&data)
+                .style(Style::new().fg(Color::Cyan));
+            frame.render_widget(sparkline, *area);
+        }
+    }
+}
+
+/// Show a dashboard of measurements until the user quits.
+pub fn watch(
+    dev: Device,
+    module: Module,
+    interval: Duration,
+    history_window: Duration,
+) -> anyhow::Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || read_sensor(dev, interval, sender));
+
+    let mut dashboard = Dashboard::new(module, history_window.try_into()?);
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut dashboard, &receiver);
+    ratatui::restore();
+    result
+}
+
+fn run(
+    terminal: &mut ratatui::DefaultTerminal,
+    dashboard: &mut Dashboard,
+    receiver: &Receiver<Update>,
+) -> anyhow::Result<()> {
+    loop {
+        for update in receiver.try_iter() {
+            dashboard.update(update);
+        }
+        terminal.draw(|frame| dashboard.render(frame))?;
+
+        if event::poll(POLL_INTERVAL)? {
+            if let Event::Key(key) = event::read()? {
+                let ctrl_c =
+                    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                if key.kind == KeyEventKind::Press
+                    && (matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) || ctrl_c)
+                {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Read the sensor every interval until the dashboard exits.
+fn read_sensor(mut dev: Device, interval: Duration, dest: Sender<Update>) {
+    loop {
+        let (update, delay) = match device::read_measurement(&mut dev) {
+            Ok(measurement) => (
+                Update::Measurement(OffsetDateTime::now_utc(), measurement),
+                interval,
+            ),
+            Err(e) => (Update::Error(e), Duration::from_millis(1100)),
+        };
+        if dest.send(update).is_err() {
+            return;
+        }
+        std::thread::sleep(delay);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use apc1_embedded::Apc1Sensor;
+    use apc1_mock::{MockI2c, Simulator};
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use super::*;
+
+    #[test]
+    fn render() {
+        let module = Simulator::new().module;
+        let measurement = Simulator::new().readings;
+        let mut dashboard = Dashboard::new(module, time::Duration::minutes(10));
+        dashboard.update(Update::Measurement(OffsetDateTime::UNIX_EPOCH, measurement));
+        dashboard.update(Update::Error(
+            anyhow::Error::new(apc1_core::Error::Device(DeviceErrorCode::from_bits(0x08)))
+                .context("Measurement reading was invalid"),
+        ));
+        // A laser fault, reported by an APC1-I, replaces the fan fault.
+        let mut simulator = Simulator::new();
+        simulator.faults = DeviceErrorCode::from_bits(0x10);
+        let mut dev = Device::I2c(Apc1Sensor::new(MockI2c::new(simulator)));
+        dashboard.update(Update::Error(
+            device::read_measurement(&mut dev).unwrap_err(),
+        ));
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        terminal.draw(|frame| dashboard.render(frame)).unwrap();
+
+        let screen = terminal
+            .backend()
+            .buffer()
+            .content()
+            .chunks(120)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>();
+        let find = |text: &str| screen.iter().find(|row| row.contains(text));
+        assert!(find("APC1-I (serial number 607609401092424838)").is_some());
+        assert!(find("eCO2         429 ppm").is_some());
+        assert!(find("Temperature  20.2°C").is_some());
+        assert!(find("EPA AQI      0 (Good, PM2.5)").is_some());
+        assert!(find("fan stopped").unwrap().contains("ok"));
+        assert!(find("laser error").unwrap().contains("FAULT"));
+        assert!(find("Read errors: 2").is_some());
+    }
+}
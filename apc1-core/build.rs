@@ -0,0 +1,10 @@
+fn main() {
+    #[cfg(feature = "protobuf")]
+    {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("No vendored protoc available");
+        prost_build::Config::new()
+            .protoc_executable(protoc)
+            .compile_protos(&["proto/apc1/v1/apc1.proto"], &["proto/"])
+            .expect("Failed to compile protobuf definitions");
+    }
+}
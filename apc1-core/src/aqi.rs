@@ -0,0 +1,275 @@
+//! The US EPA Air Quality Index for particulate matter.
+//!
+//! The device's own AQI is the UBA classification of TVOC, from 1 to 5. This
+//! computes the more widely recognized EPA AQI, from 0 to 500, from the PM2.5
+//! and PM10 concentrations, using the breakpoints revised in 2024.
+//!
+//! The EPA defines the AQI over a 24 hour average, and reports a NowCast of it
+//! from the last 12 hourly averages. [`AirQualityIndex::from_measurement`]
+//! applies the breakpoints to a single measurement, which is a reasonable
+//! indication of current conditions but will fluctuate more than the official
+//! figure. For that, average measurements by the hour and use [`nowcast`].
+//!
+//! Only `core` is used, so this works on microcontrollers as well.
+use core::fmt::Display;
+
+use crate::Measurement;
+
+/// The pollutants the AQI can be computed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pollutant {
+    /// Particles with a diameter of 2.5 micrometers or less.
+    Pm2_5,
+    /// Particles with a diameter of 10 micrometers or less.
+    Pm10,
+}
+
+/// A concentration breakpoint, in tenths of a µg/m³, and the index it maps to.
+struct Breakpoint {
+    concentration_low: u32,
+    concentration_high: u32,
+    index_low: u16,
+    index_high: u16,
+}
+
+const fn breakpoint(
+    concentration_low: u32,
+    concentration_high: u32,
+    index_low: u16,
+    index_high: u16,
+) -> Breakpoint {
+    Breakpoint {
+        concentration_low,
+        concentration_high,
+        index_low,
+        index_high,
+    }
+}
+
+const PM2_5_BREAKPOINTS: [Breakpoint; 6] = [
+    breakpoint(0, 90, 0, 50),
+    breakpoint(91, 354, 51, 100),
+    breakpoint(355, 554, 101, 150),
+    breakpoint(555, 1254, 151, 200),
+    breakpoint(1255, 2254, 201, 300),
+    breakpoint(2255, 3254, 301, 500),
+];
+
+const PM10_BREAKPOINTS: [Breakpoint; 6] = [
+    breakpoint(0, 540, 0, 50),
+    breakpoint(550, 1540, 51, 100),
+    breakpoint(1550, 2540, 101, 150),
+    breakpoint(2550, 3540, 151, 200),
+    breakpoint(3550, 4240, 201, 300),
+    breakpoint(4250, 6040, 301, 500),
+];
+
+/// The highest value of the index; concentrations beyond the last breakpoint
+/// are reported as this.
+pub const MAX_INDEX: u16 = 500;
+
+impl Pollutant {
+    /// The index for a concentration of the pollutant in µg/m³.
+    pub fn index(self, concentration: f32) -> AirQualityIndex {
+        // Float error shouldn't push a value like 35.4 down to 35.3 when truncating.
+        let tenths = (concentration * 10.0 + 0.001) as u32;
+        let (breakpoints, tenths) = match self {
+            // PM2.5 is truncated to one decimal place, and PM10 to an integer.
+            Self::Pm2_5 => (&PM2_5_BREAKPOINTS, tenths),
+            Self::Pm10 => (&PM10_BREAKPOINTS, tenths / 10 * 10),
+        };
+        let value = breakpoints
+            .iter()
+            .find(|breakpoint| tenths <= breakpoint.concentration_high)
+            .map_or(MAX_INDEX, |breakpoint| {
+                // Between breakpoints, such as 9.05 for PM2.5, use the lower category.
+                let tenths = tenths.max(breakpoint.concentration_low);
+                let index_range = u32::from(breakpoint.index_high - breakpoint.index_low);
+                let concentration_range =
+                    breakpoint.concentration_high - breakpoint.concentration_low;
+                let offset = index_range * (tenths - breakpoint.concentration_low);
+                // Round to the nearest integer.
+                let offset = (offset + concentration_range / 2) / concentration_range;
+                breakpoint.index_low + offset as u16
+            });
+
+        AirQualityIndex {
+            value,
+            category: Category::from_index(value),
+            pollutant: self,
+        }
+    }
+}
+
+impl Display for Pollutant {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Pm2_5 => write!(f, "PM2.5"),
+            Self::Pm10 => write!(f, "PM10"),
+        }
+    }
+}
+
+/// The EPA's description of an AQI range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Category {
+    /// 0 to 50.
+    Good,
+    /// 51 to 100.
+    Moderate,
+    /// 101 to 150.
+    UnhealthyForSensitiveGroups,
+    /// 151 to 200.
+    Unhealthy,
+    /// 201 to 300.
+    VeryUnhealthy,
+    /// 301 and above.
+    Hazardous,
+}
+
+impl Category {
+    pub const fn from_index(value: u16) -> Self {
+        match value {
+            0..=50 => Self::Good,
+            51..=100 => Self::Moderate,
+            101..=150 => Self::UnhealthyForSensitiveGroups,
+            151..=200 => Self::Unhealthy,
+            201..=300 => Self::VeryUnhealthy,
+            _ => Self::Hazardous,
+        }
+    }
+}
+
+impl Display for Category {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let name = match self {
+            Self::Good => "Good",
+            Self::Moderate => "Moderate",
+            Self::UnhealthyForSensitiveGroups => "Unhealthy for Sensitive Groups",
+            Self::Unhealthy => "Unhealthy",
+            Self::VeryUnhealthy => "Very Unhealthy",
+            Self::Hazardous => "Hazardous",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A value of the EPA Air Quality Index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AirQualityIndex {
+    /// The index, from 0 to 500.
+    pub value: u16,
+    pub category: Category,
+    /// The pollutant the index was determined by.
+    pub pollutant: Pollutant,
+}
+
+impl AirQualityIndex {
+    /// The index of a single measurement: the greater of the PM2.5 and PM10
+    /// indices, using the concentrations in atmospheric environment.
+    ///
+    /// Returns `None` if either concentration is outside the sensor's range.
+    pub fn from_measurement(measurement: &Measurement) -> Option<Self> {
+        let pm2_5 = measurement.pm2_5_in_air_concentration()?;
+        let pm10 = measurement.pm10_in_air_concentration()?;
+        let pm2_5 = Pollutant::Pm2_5.index(pm2_5.micrograms_per_cubic_meter().into());
+        let pm10 = Pollutant::Pm10.index(pm10.micrograms_per_cubic_meter().into());
+        Some(if pm10.value > pm2_5.value {
+            pm10
+        } else {
+            pm2_5
+        })
+    }
+}
+
+impl Display for AirQualityIndex {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} ({}, {})", self.value, self.category, self.pollutant)
+    }
+}
+
+/// The NowCast concentration from up to 12 hourly average concentrations of
+/// PM2.5 or PM10, most recent first, with `None` for hours without data.
+///
+/// Recent hours are weighted more heavily the more the concentration has been
+/// changing. Returns `None` if two of the three most recent hours are
+/// missing, as the EPA requires.
+pub fn nowcast(hourly: &[Option<f32>]) -> Option<f32> {
+    let hourly = &hourly[..hourly.len().min(12)];
+    if hourly.iter().take(3).flatten().count() < 2 {
+        return None;
+    }
+
+    let (min, max) = hourly
+        .iter()
+        .flatten()
+        .fold((f32::MAX, f32::MIN), |(min, max), &c| {
+            (min.min(c), max.max(c))
+        });
+    let weight = if max > 0.0 { min / max } else { 1.0 };
+    // The minimum weight for particulate matter.
+    let weight = weight.max(0.5);
+
+    let mut factor = 1.0;
+    let mut total = 0.0;
+    let mut weights = 0.0;
+    for concentration in hourly {
+        if let Some(concentration) = concentration {
+            total += factor * concentration;
+            weights += factor;
+        }
+        factor *= weight;
+    }
+    Some(total / weights)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::response::test::MEASUREMENT;
+
+    #[test]
+    fn pm2_5_index() {
+        let index = |c| Pollutant::Pm2_5.index(c).value;
+        assert_eq!(index(0.0), 0);
+        assert_eq!(index(9.0), 50);
+        assert_eq!(index(9.05), 50);
+        assert_eq!(index(9.1), 51);
+        assert_eq!(index(35.0), 99);
+        assert_eq!(index(35.4), 100);
+        assert_eq!(index(35.5), 101);
+        assert_eq!(index(325.4), 500);
+        assert_eq!(index(1000.0), 500);
+        assert_eq!(Pollutant::Pm2_5.index(60.0).category, Category::Unhealthy);
+    }
+
+    #[test]
+    fn pm10_index() {
+        let index = |c| Pollutant::Pm10.index(c).value;
+        assert_eq!(index(54.9), 50);
+        assert_eq!(index(55.0), 51);
+        assert_eq!(index(100.0), 73);
+        assert_eq!(index(604.0), 500);
+    }
+
+    #[test]
+    fn from_measurement() {
+        let measurement = Measurement::try_from(&MEASUREMENT).unwrap();
+        let index = AirQualityIndex::from_measurement(&measurement).unwrap();
+        assert_eq!(index.value, 0);
+        assert_eq!(index.category, Category::Good);
+    }
+
+    #[test]
+    fn nowcast_weights_recent_hours() {
+        assert_eq!(nowcast(&[Some(10.0); 12]), Some(10.0));
+        // The range is large, so the weight is the minimum of 0.5.
+        let concentration = nowcast(&[Some(20.0), Some(10.0)]).unwrap();
+        assert!((concentration - 50.0 / 3.0).abs() < 0.001);
+        // A missing hour is skipped, but still reduces the weight of older hours.
+        let concentration = nowcast(&[Some(20.0), None, Some(10.0)]).unwrap();
+        assert!((concentration - 22.5 / 1.25).abs() < 0.001);
+        assert_eq!(nowcast(&[Some(20.0), None, None, Some(10.0)]), None);
+        assert_eq!(nowcast(&[]), None);
+    }
+}
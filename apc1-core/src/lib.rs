@@ -1,9 +1,33 @@
+//! Types and parsers for the ScioSense APC1 protocol.
+//!
+//! This crate is `no_std` and doesn't allocate. The `alloc` feature adds
+//! conversions to owned types such as `String`, and `std` implies it.
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod aqi;
+mod parser;
+#[cfg(feature = "protobuf")]
+pub mod proto;
 mod request;
 mod response;
+pub mod units;
 
+pub use parser::{Frame, FrameParser, Frames};
 pub use request::{i2c, uart};
-pub use response::{DeviceErrorCode, Measurement, Module};
+pub use response::{
+    DeviceErrorCode, DeviceFault, Measurement, MeasurementBuilder, Module, ModuleBuilder,
+    ModuleName,
+};
 
+/// Errors that can occur when decoding responses from the APC1.
+///
+/// This implements [`core::error::Error`], so it is usable without the
+/// standard library.
 #[derive(thiserror::Error, Debug, PartialEq)]
 pub enum Error {
     #[error("Reading was missing the expected frame header")]
@@ -12,4 +36,62 @@ pub enum Error {
     Checksum { expected: u16, actual: u16 },
     #[error("Device in error state: {0}")]
     Device(DeviceErrorCode),
+    #[error("Frame was truncated: expected {expected} bytes, got {actual}")]
+    Truncated { expected: usize, actual: usize },
+}
+
+impl Error {
+    /// A stable numeric code identifying the kind of error.
+    ///
+    /// These are intended for FFI and other language bindings that need to
+    /// map failures without inspecting the Rust type. Codes are never reused
+    /// or renumbered, and 0 is reserved to mean "no error".
+    ///
+    /// | Code | Variant                  |
+    /// |------|--------------------------|
+    /// | 1    | [`Error::InvalidHeader`] |
+    /// | 2    | [`Error::Checksum`]      |
+    /// | 3    | [`Error::Device`]        |
+    /// | 4    | [`Error::Truncated`]     |
+    pub const fn as_code(&self) -> u16 {
+        match self {
+            Error::InvalidHeader => 1,
+            Error::Checksum { .. } => 2,
+            Error::Device(_) => 3,
+            Error::Truncated { .. } => 4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn error_codes_are_stable() {
+        assert_eq!(Error::InvalidHeader.as_code(), 1);
+        assert_eq!(
+            Error::Checksum {
+                expected: 0,
+                actual: 1
+            }
+            .as_code(),
+            2
+        );
+        assert_eq!(Error::Device(DeviceErrorCode::from_bits(0x08)).as_code(), 3);
+        assert_eq!(
+            Error::Truncated {
+                expected: 64,
+                actual: 4
+            }
+            .as_code(),
+            4
+        );
+    }
+
+    #[test]
+    fn error_implements_core_error() {
+        fn assert_error<E: core::error::Error>() {}
+        assert_error::<Error>();
+    }
 }
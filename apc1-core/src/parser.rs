@@ -0,0 +1,242 @@
+//! Incremental parsing of responses from a byte stream.
+//!
+//! The UART variant streams responses over a serial port, so reads rarely line
+//! up with frame boundaries and the stream may contain noise or partial frames
+//! (for example, right after the port is opened). [`FrameParser`] accepts bytes
+//! in whatever chunks they arrive, locates frames by their header and length,
+//! and validates each one before yielding it.
+use crate::{
+    response::{HEADER, MEASUREMENT_LEN, MODULE_LEN},
+    Error, Measurement, Module,
+};
+
+/// A response from the APC1.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Frame {
+    Measurement(Measurement),
+    Module(Module),
+}
+
+/// A push-based parser for a stream of APC1 responses.
+///
+/// ```
+/// use apc1_core::{Frame, FrameParser};
+///
+/// let mut parser = FrameParser::new();
+/// # let chunk: &[u8] = &[];
+/// for frame in parser.feed(chunk) {
+///     match frame {
+///         Ok(Frame::Measurement(measurement)) => println!("{}", measurement),
+///         Ok(Frame::Module(module)) => println!("{}", module),
+///         Err(e) => eprintln!("Bad frame: {}", e),
+///     }
+/// }
+/// ```
+///
+/// Bytes that cannot be the start of a frame are discarded. Frames that fail
+/// validation are reported as errors, after which the parser searches for a
+/// frame header inside the rejected frame, so a corrupt or truncated frame
+/// never causes a following valid frame to be lost.
+#[derive(Debug, Clone)]
+pub struct FrameParser {
+    buf: [u8; MEASUREMENT_LEN],
+    len: usize,
+}
+
+impl Default for FrameParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameParser {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MEASUREMENT_LEN],
+            len: 0,
+        }
+    }
+
+    /// Add bytes read from the device to the parser.
+    ///
+    /// The returned iterator yields each frame completed by `data`. It should
+    /// be run to completion; any of `data` not yet examined when it is dropped
+    /// is discarded.
+    pub fn feed<'a>(&'a mut self, data: &'a [u8]) -> Frames<'a> {
+        Frames { parser: self, data }
+    }
+
+    /// Discard any partially received frame.
+    pub fn reset(&mut self) {
+        self.len = 0;
+    }
+
+    /// The number of bytes buffered while waiting for the rest of a frame.
+    pub fn buffered(&self) -> usize {
+        self.len
+    }
+
+    /// Return the frame at the start of the buffer, if it's complete.
+    ///
+    /// Any bytes before the first possible frame header are discarded.
+    fn next_frame(&mut self) -> Option<Result<Frame, Error>> {
+        loop {
+            let start = (0..self.len)
+                .find(|&i| {
+                    self.buf[i] == HEADER[0] && (i + 1 == self.len || self.buf[i + 1] == HEADER[1])
+                })
+                .unwrap_or(self.len);
+            self.discard(start);
+            if self.len < 4 {
+                return None;
+            }
+
+            let frame_len = match u16::from_be_bytes([self.buf[2], self.buf[3]]) as usize + 4 {
+                len @ (MEASUREMENT_LEN | MODULE_LEN) => len,
+                _ => {
+                    // Not a real header; look for one in what follows.
+                    self.discard(1);
+                    continue;
+                }
+            };
+            if self.len < frame_len {
+                return None;
+            }
+
+            let result = match frame_len {
+                MEASUREMENT_LEN => self
+                    .buf
+                    .first_chunk::<MEASUREMENT_LEN>()
+                    .map(Measurement::try_from)
+                    .map(|result| result.map(Frame::Measurement)),
+                _ => self
+                    .buf
+                    .first_chunk::<MODULE_LEN>()
+                    .map(Module::try_from)
+                    .map(|result| result.map(Frame::Module)),
+            }?;
+            match result {
+                // The header may have been noise, and a real frame may start inside it.
+                Err(Error::Checksum { .. }) => self.discard(1),
+                _ => self.discard(frame_len),
+            }
+            return Some(result);
+        }
+    }
+
+    /// Drop `count` bytes from the start of the buffer.
+    fn discard(&mut self, count: usize) {
+        self.buf.copy_within(count..self.len, 0);
+        self.len -= count;
+    }
+}
+
+/// Iterator over the frames completed by a chunk of data; see [`FrameParser::feed`].
+pub struct Frames<'a> {
+    parser: &'a mut FrameParser,
+    data: &'a [u8],
+}
+
+impl Iterator for Frames<'_> {
+    type Item = Result<Frame, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(frame) = self.parser.next_frame() {
+                return Some(frame);
+            }
+            // Without a complete frame buffered, the buffer always has room.
+            let (&byte, rest) = self.data.split_first()?;
+            self.data = rest;
+            self.parser.buf[self.parser.len] = byte;
+            self.parser.len += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::response::test::{MEASUREMENT, MODULE};
+
+    fn parse_all(parser: &mut FrameParser, chunks: &[&[u8]]) -> Vec<Result<Frame, Error>> {
+        chunks
+            .iter()
+            .flat_map(|chunk| parser.feed(chunk).collect::<Vec<_>>())
+            .collect()
+    }
+
+    fn measurement() -> Frame {
+        Frame::Measurement(Measurement::try_from(&MEASUREMENT).unwrap())
+    }
+
+    fn module() -> Frame {
+        Frame::Module(Module::try_from(&MODULE).unwrap())
+    }
+
+    #[test]
+    fn aligned_frames() {
+        let mut parser = FrameParser::new();
+        let frames = parse_all(&mut parser, &[&MEASUREMENT, &MODULE]);
+        assert_eq!(frames, [Ok(measurement()), Ok(module())]);
+        assert_eq!(parser.buffered(), 0);
+    }
+
+    #[test]
+    fn frames_split_across_chunks() {
+        let mut stream = MEASUREMENT.to_vec();
+        stream.extend(MODULE);
+        stream.extend(MEASUREMENT);
+
+        for chunk_size in [1, 7, 13, 63, 64, 65] {
+            let mut parser = FrameParser::new();
+            let chunks = stream.chunks(chunk_size).collect::<Vec<_>>();
+            let frames = parse_all(&mut parser, &chunks);
+            assert_eq!(frames, [Ok(measurement()), Ok(module()), Ok(measurement())]);
+        }
+    }
+
+    #[test]
+    fn leading_garbage_is_skipped() {
+        let mut parser = FrameParser::new();
+        let garbage: &[u8] = &[0x00, 0x4D, 0x42, 0x42, 0x00, 0x42, 0x4D, 0xFF, 0xFF, 0x13];
+        let frames = parse_all(&mut parser, &[garbage, &MEASUREMENT]);
+        assert_eq!(frames, [Ok(measurement())]);
+    }
+
+    #[test]
+    fn truncated_frame_is_resynchronized() {
+        // The tail of a measurement is lost, and the next frame starts where it should have been.
+        let mut stream = MEASUREMENT[..30].to_vec();
+        stream.extend(MODULE);
+        stream.extend(MEASUREMENT);
+
+        let mut parser = FrameParser::new();
+        let frames = parse_all(&mut parser, &[&stream]);
+        assert_eq!(frames.len(), 3);
+        assert!(matches!(frames[0], Err(Error::Checksum { .. })));
+        assert_eq!(frames[1..], [Ok(module()), Ok(measurement())]);
+    }
+
+    #[test]
+    fn corrupt_frame_is_reported() {
+        let mut corrupt = MEASUREMENT;
+        corrupt[11] += 1;
+
+        let mut parser = FrameParser::new();
+        let frames = parse_all(&mut parser, &[&corrupt, &MEASUREMENT]);
+        assert_eq!(
+            frames,
+            [
+                Err(Error::Checksum {
+                    expected: 2107,
+                    actual: 2108
+                }),
+                Ok(measurement())
+            ]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! Protobuf encoding of APC1 data.
+//!
+//! The schema lives in `proto/apc1/v1/apc1.proto` in this crate and is the
+//! contract for consumers in other languages. The types in [`v1`] are generated
+//! from it with prost; convert to them using [`From`], and back with
+//! [`TryFrom`], which fails if a field holds a value the APC1 can't send.
+//! The schema leaves out the measurement's reserved bytes, so they are zero
+//! after a round trip.
+
+/// Types generated from the `apc1.v1` protobuf package.
+pub mod v1 {
+    include!(concat!(env!("OUT_DIR"), "/apc1.v1.rs"));
+}
+
+impl From<&crate::Measurement> for v1::Measurement {
+    fn from(value: &crate::Measurement) -> Self {
+        Self {
+            pm1: value.pm1_0.into(),
+            pm2_5: value.pm2_5.into(),
+            pm10: value.pm10.into(),
+            pm1_in_air: value.pm1_0_in_air.into(),
+            pm2_5_in_air: value.pm2_5_in_air.into(),
+            pm10_in_air: value.pm10_in_air.into(),
+            um_0_3_particles: value.um_0_3_particles.into(),
+            um_0_5_particles: value.um_0_5_particles.into(),
+            um_1_particles: value.um_1_particles.into(),
+            um_2_5_particles: value.um_2_5_particles.into(),
+            um_5_particles: value.um_5_particles.into(),
+            um_10_particles: value.um_10_particles.into(),
+            tvoc: value.tvoc.into(),
+            eco2: value.eco2.into(),
+            t_comp: value.t_comp.into(),
+            rh_comp: value.rh_comp.into(),
+            t_raw: value.t_raw.into(),
+            rh_raw: value.rh_raw.into(),
+            rs_0: value.rs_0,
+            rs_1: value.rs_1,
+            rs_2: value.rs_2,
+            rs_3: value.rs_3,
+            aqi: value.aqi.into(),
+            version: value.version.into(),
+        }
+    }
+}
+
+impl From<crate::Measurement> for v1::Measurement {
+    fn from(value: crate::Measurement) -> Self {
+        Self::from(&value)
+    }
+}
+
+impl From<&crate::Module> for v1::Module {
+    fn from(value: &crate::Module) -> Self {
+        Self {
+            name_and_type: value.name_and_type.into(),
+            serial_number: value.serial_number,
+            delimiter: value.delimiter.into(),
+            fw_version_major: value.fw_version_major.into(),
+            fw_version_minor: value.fw_version_minor.into(),
+        }
+    }
+}
+
+impl From<crate::Module> for v1::Module {
+    fn from(value: crate::Module) -> Self {
+        Self::from(&value)
+    }
+}
+
+/// A protobuf message field holds a value that doesn't fit the APC1's type for it.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("The {field} field is out of range")]
+pub struct OutOfRange {
+    /// The name of the field in the protobuf schema.
+    pub field: &'static str,
+}
+
+/// Narrow a protobuf integer to the type the APC1 uses for `field`.
+fn narrow<T: TryFrom<u32>>(field: &'static str, value: u32) -> Result<T, OutOfRange> {
+    T::try_from(value).map_err(|_| OutOfRange { field })
+}
+
+impl TryFrom<&v1::Measurement> for crate::Measurement {
+    type Error = OutOfRange;
+
+    fn try_from(value: &v1::Measurement) -> Result<Self, Self::Error> {
+        Ok(crate::Measurement::builder()
+            .pm1_0(narrow("pm1", value.pm1)?)
+            .pm2_5(narrow("pm2_5", value.pm2_5)?)
+            .pm10(narrow("pm10", value.pm10)?)
+            .pm1_0_in_air(narrow("pm1_in_air", value.pm1_in_air)?)
+            .pm2_5_in_air(narrow("pm2_5_in_air", value.pm2_5_in_air)?)
+            .pm10_in_air(narrow("pm10_in_air", value.pm10_in_air)?)
+            .um_0_3_particles(narrow("um_0_3_particles", value.um_0_3_particles)?)
+            .um_0_5_particles(narrow("um_0_5_particles", value.um_0_5_particles)?)
+            .um_1_particles(narrow("um_1_particles", value.um_1_particles)?)
+            .um_2_5_particles(narrow("um_2_5_particles", value.um_2_5_particles)?)
+            .um_5_particles(narrow("um_5_particles", value.um_5_particles)?)
+            .um_10_particles(narrow("um_10_particles", value.um_10_particles)?)
+            .tvoc(narrow("tvoc", value.tvoc)?)
+            .eco2(narrow("eco2", value.eco2)?)
+            .t_comp(narrow("t_comp", value.t_comp)?)
+            .rh_comp(narrow("rh_comp", value.rh_comp)?)
+            .t_raw(narrow("t_raw", value.t_raw)?)
+            .rh_raw(narrow("rh_raw", value.rh_raw)?)
+            .rs_0(value.rs_0)
+            .rs_1(value.rs_1)
+            .rs_2(value.rs_2)
+            .rs_3(value.rs_3)
+            .aqi(narrow("aqi", value.aqi)?)
+            .version(narrow("version", value.version)?)
+            .build())
+    }
+}
+
+impl TryFrom<v1::Measurement> for crate::Measurement {
+    type Error = OutOfRange;
+
+    fn try_from(value: v1::Measurement) -> Result<Self, Self::Error> {
+        Self::try_from(&value)
+    }
+}
+
+impl TryFrom<&v1::Module> for crate::Module {
+    type Error = OutOfRange;
+
+    /// The name and type must be six ASCII characters, and the delimiter a
+    /// single character the device can send.
+    fn try_from(value: &v1::Module) -> Result<Self, Self::Error> {
+        let name_and_type = <[u8; 6]>::try_from(value.name_and_type.as_bytes())
+            .ok()
+            .filter(|name| name.is_ascii())
+            .ok_or(OutOfRange {
+                field: "name_and_type",
+            })?;
+        let mut delimiter = value.delimiter.chars();
+        let delimiter = match (delimiter.next(), delimiter.next()) {
+            (Some(c), None) if u8::try_from(c).is_ok() => c,
+            _ => return Err(OutOfRange { field: "delimiter" }),
+        };
+        Ok(crate::Module::builder()
+            .name_and_type(crate::ModuleName::from_bytes(name_and_type))
+            .serial_number(value.serial_number)
+            .delimiter(delimiter)
+            .fw_version_major(narrow("fw_version_major", value.fw_version_major)?)
+            .fw_version_minor(narrow("fw_version_minor", value.fw_version_minor)?)
+            .build())
+    }
+}
+
+impl TryFrom<v1::Module> for crate::Module {
+    type Error = OutOfRange;
+
+    fn try_from(value: v1::Module) -> Result<Self, Self::Error> {
+        Self::try_from(&value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use prost::Message;
+
+    use super::{v1, OutOfRange};
+    use crate::response::test::{MEASUREMENT, MODULE};
+    use crate::Module;
+
+    #[test]
+    fn module_round_trip() {
+        let module = Module::try_from(&MODULE).unwrap();
+
+        let encoded = v1::Module::from(&module).encode_to_vec();
+        let decoded = v1::Module::decode(encoded.as_slice()).unwrap();
+
+        assert_eq!(decoded.name_and_type, "APC1-I");
+        assert_eq!(decoded.serial_number, 607609401092424838_u64);
+        assert_eq!(decoded.delimiter, "-");
+        assert_eq!(decoded.fw_version_major, 0);
+        assert_eq!(decoded.fw_version_minor, 35);
+        assert_eq!(Module::try_from(decoded), Ok(module));
+    }
+
+    #[test]
+    fn measurement_fields() {
+        let measurement = crate::Measurement::try_from(&MEASUREMENT).unwrap();
+
+        let encoded = v1::Measurement::from(&measurement).encode_to_vec();
+        let decoded = v1::Measurement::decode(encoded.as_slice()).unwrap();
+
+        assert_eq!(decoded.um_0_3_particles, 336);
+        assert_eq!(decoded.tvoc, 37);
+        assert_eq!(decoded.eco2, 429);
+        assert_eq!(decoded.t_comp, 202);
+        assert_eq!(decoded.rs_0, 222610);
+        assert_eq!(decoded.aqi, 1);
+        assert_eq!(decoded.version, 35);
+        let converted = crate::Measurement::try_from(&decoded).unwrap();
+        assert_eq!(v1::Measurement::from(converted), decoded);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let measurement = v1::Measurement {
+            eco2: 70_000,
+            ..Default::default()
+        };
+        assert_eq!(
+            crate::Measurement::try_from(measurement),
+            Err(OutOfRange { field: "eco2" })
+        );
+
+        let mut module = v1::Module {
+            name_and_type: "APC1-I".into(),
+            delimiter: "-".into(),
+            fw_version_minor: 256,
+            ..Default::default()
+        };
+        assert_eq!(
+            Module::try_from(&module),
+            Err(OutOfRange {
+                field: "fw_version_minor"
+            })
+        );
+        module.fw_version_minor = 35;
+        module.name_and_type = "APC1".into();
+        assert_eq!(
+            Module::try_from(&module),
+            Err(OutOfRange {
+                field: "name_and_type"
+            })
+        );
+        module.name_and_type = "APC1-I".into();
+        module.delimiter = "--".into();
+        assert_eq!(
+            Module::try_from(&module),
+            Err(OutOfRange { field: "delimiter" })
+        );
+    }
+}
@@ -1,25 +1,25 @@
-/// Commands the host can send to the APC1.
-///
-/// Each command is 7 bytes in length. The format of each command is:
-///
-/// -----------------------------------------------
-/// | 2 bytes      | 1 byte  | 2 bytes | 2 bytes  |
-/// -----------------------------------------------
-/// | Magic Number | Command |  Mode   | Checksum |
-/// -----------------------------------------------
-///
-/// The command is in big-endian byte order. The checksum applies to all bytes in the command
-/// except the checksum itself.
-///
-/// The device responds in the following format:
-///
-/// ---------------------------------------------------------
-/// | 2 bytes      | 2 bytes      | Variable     | 2 bytes  |
-/// ---------------------------------------------------------
-/// | Magic Number | Frame length | Mode or Data | Checksum |
-/// ---------------------------------------------------------
-///
-/// The response is also in big-endian byte order. The response size depends on the request.
+//! Commands the host can send to the APC1.
+//!
+//! Each command is 7 bytes in length. The format of each command is:
+//!
+//! -----------------------------------------------
+//! | 2 bytes      | 1 byte  | 2 bytes | 2 bytes  |
+//! -----------------------------------------------
+//! | Magic Number | Command |  Mode   | Checksum |
+//! -----------------------------------------------
+//!
+//! The command is in big-endian byte order. The checksum applies to all bytes in the command
+//! except the checksum itself.
+//!
+//! The device responds in the following format:
+//!
+//! ---------------------------------------------------------
+//! | 2 bytes      | 2 bytes      | Variable     | 2 bytes  |
+//! ---------------------------------------------------------
+//! | Magic Number | Frame length | Mode or Data | Checksum |
+//! ---------------------------------------------------------
+//!
+//! The response is also in big-endian byte order. The response size depends on the request.
 
 /// The magic number that starts each command.
 const MAGIC: &[u8; 2] = &[0x42, 0x4D];
@@ -55,6 +55,9 @@ pub mod i2c {
     /// The base Write Register Address commands should be written to.
     pub const COMMAND_BASE_ADDR: u8 = 0x40;
 
+    /// The base Read Register Address the response to [`Command::ReadModuleId`] is read from.
+    pub const RESPONSE_BASE_ADDR: u8 = 0x47;
+
     // The I2C variant supports this option with the [`TOGGLE_DEVICE_MODE`] command.
     const RESET_DEVICE: u8 = 0x0F;
 
@@ -252,7 +255,7 @@ pub mod uart {
     }
 }
 
-fn calculate_checksum(payload: &[u8]) -> [u8; 2] {
+pub(crate) fn calculate_checksum(payload: &[u8]) -> [u8; 2] {
     let checksum: u16 = payload
         .iter()
         .fold(0_u16, |checksum, elem| checksum.wrapping_add(*elem as u16));
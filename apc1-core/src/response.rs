@@ -16,14 +16,28 @@
 /// Checksum is the sum of the values of all bytes sent, excluding the checksum itself.
 ///
 /// All values are big endian.
-use std::fmt::Display;
+use core::fmt::Display;
+
+use crate::request::calculate_checksum;
+use crate::units::{MassConcentration, ParticleCount, RelativeHumidity, Temperature};
+
+/// The frame header that starts every response.
+pub(crate) const HEADER: [u8; 2] = [0x42, 0x4D];
+
+/// Total size of a measurement frame, including the header, length, and checksum.
+pub(crate) const MEASUREMENT_LEN: usize = 64;
+
+/// Total size of a module ID frame, including the header, length, and checksum.
+pub(crate) const MODULE_LEN: usize = 23;
 
 /// Measurement data from the APC1.
 ///
 /// This is based on the structure documented in Section 8.2.1 of the APC1
 /// Datasheet. Each measurement is 64 bytes and is updated every second when
 /// the device is in Measurement mode.
-#[derive(Debug, PartialEq, Eq)]
+///
+/// Use [`Measurement::builder`] to create one without a frame to parse.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Measurement {
     /// PM1.0 mass concentration in ug/m3; range 0-500.
     pub pm1_0: u16,
@@ -108,8 +122,9 @@ impl TryFrom<&[u8; 64]> for Measurement {
             return Err(Self::Error::InvalidHeader);
         }
 
-        if value[61] != 0x00 {
-            return Err(Self::Error::Device(DeviceErrorCode(value[61])));
+        let faults = DeviceErrorCode(value[61]);
+        if !faults.is_empty() {
+            return Err(Self::Error::Device(faults));
         }
 
         Ok(Self {
@@ -117,7 +132,7 @@ impl TryFrom<&[u8; 64]> for Measurement {
             pm2_5: u16::from_be_bytes([value[6], value[7]]),
             pm10: u16::from_be_bytes([value[8], value[9]]),
             pm1_0_in_air: u16::from_be_bytes([value[10], value[11]]),
-            pm2_5_in_air: u16::from_be_bytes([value[12], value[12]]),
+            pm2_5_in_air: u16::from_be_bytes([value[12], value[13]]),
             pm10_in_air: u16::from_be_bytes([value[14], value[15]]),
             um_0_3_particles: u16::from_be_bytes([value[16], value[17]]),
             um_0_5_particles: u16::from_be_bytes([value[18], value[19]]),
@@ -143,8 +158,239 @@ impl TryFrom<&[u8; 64]> for Measurement {
     }
 }
 
+/// Parse the first measurement frame in the slice.
+///
+/// The frame starts at the first frame header with a measurement's length,
+/// and bytes before it and after the frame are ignored. Unlike
+/// [`Measurement::find_in`], which skips frames that fail validation, this
+/// returns the reason that frame was rejected.
+impl TryFrom<&[u8]> for Measurement {
+    type Error = crate::Error;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        locate::<MEASUREMENT_LEN>(value).and_then(Self::try_from)
+    }
+}
+
+impl Measurement {
+    /// Find the first valid measurement frame in `buf`.
+    ///
+    /// Returns the measurement and the number of bytes consumed, which
+    /// includes any bytes before the frame. Frames that fail validation,
+    /// including those reporting a device fault, are skipped; parse with
+    /// [`TryFrom`] to learn why a frame was rejected.
+    pub fn find_in(buf: &[u8]) -> Option<(Self, usize)> {
+        find_in::<_, MEASUREMENT_LEN>(buf, |frame| Self::try_from(frame))
+    }
+
+    /// Start building a measurement; fields that aren't set are zero.
+    pub fn builder() -> MeasurementBuilder {
+        MeasurementBuilder(Self::default())
+    }
+
+    /// Encode the measurement as the frame the device sends, which parses
+    /// back to an equal measurement.
+    pub fn to_bytes(&self) -> [u8; MEASUREMENT_LEN] {
+        self.to_bytes_with_faults(DeviceErrorCode::default())
+    }
+
+    /// Encode the measurement as the frame sent by a device reporting `faults`.
+    ///
+    /// Parsing the frame fails with [`crate::Error::Device`] if any fault is set.
+    pub fn to_bytes_with_faults(&self, faults: DeviceErrorCode) -> [u8; MEASUREMENT_LEN] {
+        let mut frame = [0; MEASUREMENT_LEN];
+        frame[..4].copy_from_slice(&[HEADER[0], HEADER[1], 0x00, 0x3C]);
+        let words = [
+            self.pm1_0,
+            self.pm2_5,
+            self.pm10,
+            self.pm1_0_in_air,
+            self.pm2_5_in_air,
+            self.pm10_in_air,
+            self.um_0_3_particles,
+            self.um_0_5_particles,
+            self.um_1_particles,
+            self.um_2_5_particles,
+            self.um_5_particles,
+            self.um_10_particles,
+            self.tvoc,
+            self.eco2,
+            self._reserved,
+            self.t_comp,
+            self.rh_comp,
+            self.t_raw,
+            self.rh_raw,
+        ];
+        for (dest, word) in frame[4..42].chunks_exact_mut(2).zip(words) {
+            dest.copy_from_slice(&word.to_be_bytes());
+        }
+        for (dest, long) in frame[42..58]
+            .chunks_exact_mut(4)
+            .zip([self.rs_0, self.rs_1, self.rs_2, self.rs_3])
+        {
+            dest.copy_from_slice(&long.to_be_bytes());
+        }
+        frame[58] = self.aqi;
+        frame[59] = self.__reserved;
+        frame[60] = self.version;
+        frame[61] = faults.bits();
+        let (payload, checksum) = frame.split_at_mut(MEASUREMENT_LEN - 2);
+        checksum.copy_from_slice(&calculate_checksum(payload));
+        frame
+    }
+
+    /// Temperature, compensated for the heat of the module itself.
+    pub fn temperature(&self) -> Temperature {
+        Temperature::from_decidegrees_celsius(self.t_comp)
+    }
+
+    /// Relative humidity, compensated for the heat of the module itself.
+    pub fn humidity(&self) -> RelativeHumidity {
+        RelativeHumidity::from_per_mille(self.rh_comp)
+    }
+
+    /// Temperature as measured, without compensation.
+    pub fn raw_temperature(&self) -> Temperature {
+        Temperature::from_decidegrees_celsius(self.t_raw)
+    }
+
+    /// Relative humidity as measured, without compensation.
+    pub fn raw_humidity(&self) -> RelativeHumidity {
+        RelativeHumidity::from_per_mille(self.rh_raw)
+    }
+
+    /// PM1.0 mass concentration, or `None` if it is outside the sensor's range.
+    pub fn pm1_0_concentration(&self) -> Option<MassConcentration> {
+        mass_concentration(self.pm1_0, 500)
+    }
+
+    /// PM2.5 mass concentration, or `None` if it is outside the sensor's range.
+    pub fn pm2_5_concentration(&self) -> Option<MassConcentration> {
+        mass_concentration(self.pm2_5, 1_000)
+    }
+
+    /// PM10 mass concentration, or `None` if it is outside the sensor's range.
+    pub fn pm10_concentration(&self) -> Option<MassConcentration> {
+        mass_concentration(self.pm10, 1_500)
+    }
+
+    /// PM1.0 mass concentration in atmospheric environment, or `None` if it
+    /// is outside the sensor's range.
+    pub fn pm1_0_in_air_concentration(&self) -> Option<MassConcentration> {
+        mass_concentration(self.pm1_0_in_air, 500)
+    }
+
+    /// PM2.5 mass concentration in atmospheric environment, or `None` if it
+    /// is outside the sensor's range.
+    pub fn pm2_5_in_air_concentration(&self) -> Option<MassConcentration> {
+        mass_concentration(self.pm2_5_in_air, 1_000)
+    }
+
+    /// PM10 mass concentration in atmospheric environment, or `None` if it
+    /// is outside the sensor's range.
+    pub fn pm10_in_air_concentration(&self) -> Option<MassConcentration> {
+        mass_concentration(self.pm10_in_air, 1_500)
+    }
+
+    // The particle counts span the full range of a u16, so unlike the mass
+    // concentrations there are no values to reject.
+
+    /// Particles with diameter >0.3 micrometers.
+    pub fn um_0_3_particle_count(&self) -> ParticleCount {
+        ParticleCount::from_per_deciliter(self.um_0_3_particles)
+    }
+
+    /// Particles with diameter >0.5 micrometers.
+    pub fn um_0_5_particle_count(&self) -> ParticleCount {
+        ParticleCount::from_per_deciliter(self.um_0_5_particles)
+    }
+
+    /// Particles with diameter >1.0 micrometers.
+    pub fn um_1_particle_count(&self) -> ParticleCount {
+        ParticleCount::from_per_deciliter(self.um_1_particles)
+    }
+
+    /// Particles with diameter >2.5 micrometers.
+    pub fn um_2_5_particle_count(&self) -> ParticleCount {
+        ParticleCount::from_per_deciliter(self.um_2_5_particles)
+    }
+
+    /// Particles with diameter >5.0 micrometers.
+    pub fn um_5_particle_count(&self) -> ParticleCount {
+        ParticleCount::from_per_deciliter(self.um_5_particles)
+    }
+
+    /// Particles with diameter >10.0 micrometers.
+    pub fn um_10_particle_count(&self) -> ParticleCount {
+        ParticleCount::from_per_deciliter(self.um_10_particles)
+    }
+}
+
+/// Generate a builder method for each field, which sets it and returns the builder.
+macro_rules! setters {
+    ($target:ident { $($field:ident: $ty:ty),* $(,)? }) => {
+        $(
+            #[doc = concat!("Set [`", stringify!($target), "::", stringify!($field), "`].")]
+            pub fn $field(mut self, $field: $ty) -> Self {
+                self.0.$field = $field;
+                self
+            }
+        )*
+    };
+}
+
+/// Builds a [`Measurement`], such as to encode with [`Measurement::to_bytes`].
+///
+/// ```
+/// use apc1_core::Measurement;
+///
+/// let measurement = Measurement::builder().pm2_5(12).eco2(450).aqi(1).build();
+/// let frame = measurement.to_bytes();
+/// assert_eq!(Measurement::try_from(&frame), Ok(measurement));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MeasurementBuilder(Measurement);
+
+impl MeasurementBuilder {
+    setters!(Measurement {
+        pm1_0: u16,
+        pm2_5: u16,
+        pm10: u16,
+        pm1_0_in_air: u16,
+        pm2_5_in_air: u16,
+        pm10_in_air: u16,
+        um_0_3_particles: u16,
+        um_0_5_particles: u16,
+        um_1_particles: u16,
+        um_2_5_particles: u16,
+        um_5_particles: u16,
+        um_10_particles: u16,
+        tvoc: u16,
+        eco2: u16,
+        t_comp: u16,
+        rh_comp: u16,
+        t_raw: u16,
+        rh_raw: u16,
+        rs_0: u32,
+        rs_1: u32,
+        rs_2: u32,
+        rs_3: u32,
+        aqi: u8,
+        version: u8,
+    });
+
+    pub fn build(self) -> Measurement {
+        self.0
+    }
+}
+
+/// Wrap a mass concentration if it is within the datasheet's range for the field.
+fn mass_concentration(value: u16, max: u16) -> Option<MassConcentration> {
+    (value <= max).then_some(MassConcentration::from_micrograms_per_cubic_meter(value))
+}
+
 impl Display for Measurement {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             concat!(
@@ -167,8 +413,8 @@ impl Display for Measurement {
                 "# particles >5.0μm per 0.1L of air: {}\n\t",
                 "# particles >10.0μm per 0.1L of air: {}\n\t",
             ),
-            self.t_comp as f32 / 10.0,
-            self.rh_comp as f32 / 10.0,
+            self.temperature().celsius(),
+            self.humidity().percent(),
             self.aqi,
             self.eco2,
             self.tvoc,
@@ -188,47 +434,214 @@ impl Display for Measurement {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// The faults reported by the device in a measurement.
+///
+/// Faults are defined in Section 8.2.2 of the datasheet, with one bit per fault:
+///
+/// -------------------------------------------------------------------------------------------------------------------------
+/// | Bit 7  |        Bit 6         |    Bit 5   | Bit 4 |    Bit 3    |    Bit 2   |     Bit 1     |         Bit 0         |
+/// -------------------------------------------------------------------------------------------------------------------------
+/// | Unused | Temp/Humidity Sensor | VOC Sensor | Laser | Fan Stopped | Photodiode | Fan-speed low | Too many Fan restarts |
+/// -------------------------------------------------------------------------------------------------------------------------
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct DeviceErrorCode(u8);
 
-// Displays device errors.
-//
-// 0 indicates no errors. Errors are defined in Section 8.2.2 of the datasheet.
-//
-// -------------------------------------------------------------------------------------------------------------------------
-// | Bit 7  |        Bit 6         |    Bit 5   | Bit 4 |    Bit 3    |    Bit 2   |     Bit 1     |         Bit 0         |
-// -------------------------------------------------------------------------------------------------------------------------
-// | Unused | Temp/Humidity Sensor | VOC Sensor | Laser | Fan Stopped | Photodiode | Fan-speed low | Too many Fan restarts |
-// -------------------------------------------------------------------------------------------------------------------------
+impl DeviceErrorCode {
+    /// Interpret the raw error byte from a measurement.
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    /// The raw error byte, as sent by the device.
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// Whether any fault is set.
+    pub const fn is_empty(&self) -> bool {
+        self.0 & DeviceFault::ALL_BITS == 0
+    }
+
+    /// Whether the given fault is set.
+    pub const fn contains(&self, fault: DeviceFault) -> bool {
+        self.0 & fault.bit() != 0
+    }
+
+    /// The fan failed to start after several attempts.
+    pub const fn too_many_fan_restarts(&self) -> bool {
+        self.contains(DeviceFault::TooManyFanRestarts)
+    }
+
+    /// The fan is turning slower than it should.
+    pub const fn fan_speed_low(&self) -> bool {
+        self.contains(DeviceFault::FanSpeedLow)
+    }
+
+    /// The photodiode measuring particulates has failed.
+    pub const fn photodiode_error(&self) -> bool {
+        self.contains(DeviceFault::Photodiode)
+    }
+
+    /// The fan has stopped.
+    pub const fn fan_stopped(&self) -> bool {
+        self.contains(DeviceFault::FanStopped)
+    }
+
+    /// The laser used to measure particulates has failed.
+    pub const fn laser_error(&self) -> bool {
+        self.contains(DeviceFault::Laser)
+    }
+
+    /// The sensor measuring TVOC and eCO2 has failed.
+    pub const fn voc_sensor_error(&self) -> bool {
+        self.contains(DeviceFault::VocSensor)
+    }
+
+    /// The temperature and humidity sensor has failed.
+    pub const fn temperature_humidity_sensor_error(&self) -> bool {
+        self.contains(DeviceFault::TemperatureHumiditySensor)
+    }
+
+    /// Iterate over the faults that are set, from the lowest bit to the highest.
+    pub fn faults(&self) -> impl Iterator<Item = DeviceFault> {
+        let code = *self;
+        DeviceFault::ALL
+            .into_iter()
+            .filter(move |fault| code.contains(*fault))
+    }
+}
+
 impl Display for DeviceErrorCode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            concat!(
-                "Too many fan restarts: {}\n",
-                "Fan-speed low: {}\n",
-                "Photodiode: {}\n",
-                "Fan stopped: {}\n",
-                "Laser: {}\n",
-                "VOC sensor: {}\n",
-                "Temperature or Humidity sensor: {}\n",
-            ),
-            self.0 ^ (1 << 0),
-            self.0 ^ (1 << 1),
-            self.0 ^ (1 << 2),
-            self.0 ^ (1 << 3),
-            self.0 ^ (1 << 4),
-            self.0 ^ (1 << 5),
-            self.0 ^ (1 << 6),
-        )
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no faults");
+        }
+        for (index, fault) in self.faults().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{fault}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A single fault the device can report; see [`DeviceErrorCode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceFault {
+    TooManyFanRestarts,
+    FanSpeedLow,
+    Photodiode,
+    FanStopped,
+    Laser,
+    VocSensor,
+    TemperatureHumiditySensor,
+}
+
+impl DeviceFault {
+    /// Every fault, in bit order.
+    pub const ALL: [DeviceFault; 7] = [
+        DeviceFault::TooManyFanRestarts,
+        DeviceFault::FanSpeedLow,
+        DeviceFault::Photodiode,
+        DeviceFault::FanStopped,
+        DeviceFault::Laser,
+        DeviceFault::VocSensor,
+        DeviceFault::TemperatureHumiditySensor,
+    ];
+
+    /// Bit 7 is unused, so it's not considered a fault.
+    const ALL_BITS: u8 = 0x7F;
+
+    /// The bit in [`DeviceErrorCode`] that indicates this fault.
+    pub const fn bit(&self) -> u8 {
+        match self {
+            DeviceFault::TooManyFanRestarts => 1 << 0,
+            DeviceFault::FanSpeedLow => 1 << 1,
+            DeviceFault::Photodiode => 1 << 2,
+            DeviceFault::FanStopped => 1 << 3,
+            DeviceFault::Laser => 1 << 4,
+            DeviceFault::VocSensor => 1 << 5,
+            DeviceFault::TemperatureHumiditySensor => 1 << 6,
+        }
+    }
+}
+
+impl Display for DeviceFault {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let description = match self {
+            DeviceFault::TooManyFanRestarts => "too many fan restarts",
+            DeviceFault::FanSpeedLow => "fan speed low",
+            DeviceFault::Photodiode => "photodiode error",
+            DeviceFault::FanStopped => "fan stopped",
+            DeviceFault::Laser => "laser error",
+            DeviceFault::VocSensor => "VOC sensor error",
+            DeviceFault::TemperatureHumiditySensor => "temperature or humidity sensor error",
+        };
+        write!(f, "{description}")
+    }
+}
+
+/// The module's name and type, such as `APC1-I`.
+///
+/// This is stored inline, so reading the module doesn't allocate. The bytes
+/// the device sent are kept as they are, and a copy with anything other than
+/// printable ASCII replaced by `?` is kept for display.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModuleName {
+    bytes: [u8; 6],
+    printable: [u8; 6],
+}
+
+impl ModuleName {
+    /// Create a name from the bytes the device sent.
+    pub fn from_bytes(bytes: [u8; 6]) -> Self {
+        let printable = bytes.map(|b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b
+            } else {
+                b'?'
+            }
+        });
+        Self { bytes, printable }
+    }
+
+    /// The bytes the device sent.
+    pub fn as_bytes(&self) -> &[u8; 6] {
+        &self.bytes
+    }
+
+    /// The name, with any byte that isn't printable ASCII replaced by `?`.
+    pub fn as_str(&self) -> &str {
+        // Every byte is ASCII, so this always succeeds.
+        core::str::from_utf8(&self.printable).unwrap_or_default()
+    }
+}
+
+impl core::fmt::Debug for ModuleName {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Display for ModuleName {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<ModuleName> for alloc::string::String {
+    fn from(value: ModuleName) -> Self {
+        value.as_str().into()
     }
 }
 
 /// Read the module firmware and version.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Module {
-    /// The module's name and type encoded as ASCII.
-    pub name_and_type: String,
+    /// The module's name and type.
+    pub name_and_type: ModuleName,
     /// Module serial number.
     pub serial_number: u64,
     /// The delimiter character in the name_and_type string between the name and type.
@@ -260,7 +673,9 @@ impl TryFrom<&[u8; 23]> for Module {
         }
 
         Ok(Self {
-            name_and_type: value.iter().skip(4).take(6).map(|b| *b as char).collect(),
+            name_and_type: ModuleName::from_bytes([
+                value[4], value[5], value[6], value[7], value[8], value[9],
+            ]),
             serial_number: u64::from_be_bytes([
                 value[10], value[11], value[12], value[13], value[14], value[15], value[16],
                 value[17],
@@ -272,8 +687,121 @@ impl TryFrom<&[u8; 23]> for Module {
     }
 }
 
+/// Parse the first module ID frame in the slice.
+///
+/// The frame starts at the first frame header with a module ID's length,
+/// and bytes before it and after the frame are ignored. Unlike
+/// [`Module::find_in`], which skips frames that fail validation, this
+/// returns the reason that frame was rejected.
+impl TryFrom<&[u8]> for Module {
+    type Error = crate::Error;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        locate::<MODULE_LEN>(value).and_then(Self::try_from)
+    }
+}
+
+impl Module {
+    /// Find the first valid module ID frame in `buf`.
+    ///
+    /// Returns the module and the number of bytes consumed, which includes any
+    /// bytes before the frame. Frames that fail validation are skipped.
+    pub fn find_in(buf: &[u8]) -> Option<(Self, usize)> {
+        find_in::<_, MODULE_LEN>(buf, |frame| Self::try_from(frame))
+    }
+
+    /// Start building a module ID; fields that aren't set are as they would
+    /// be parsed from zeros.
+    pub fn builder() -> ModuleBuilder {
+        ModuleBuilder(Self {
+            name_and_type: ModuleName::from_bytes([0; 6]),
+            serial_number: 0,
+            delimiter: '\0',
+            fw_version_major: 0,
+            fw_version_minor: 0,
+        })
+    }
+
+    /// Encode the module ID as the frame the device sends.
+    ///
+    /// The delimiter is sent as a single byte, so one outside Latin-1 is sent as `?`.
+    pub fn to_bytes(&self) -> [u8; MODULE_LEN] {
+        let mut frame = [0; MODULE_LEN];
+        frame[..4].copy_from_slice(&[HEADER[0], HEADER[1], 0x00, 0x13]);
+        frame[4..10].copy_from_slice(self.name_and_type.as_bytes());
+        frame[10..18].copy_from_slice(&self.serial_number.to_be_bytes());
+        frame[18] = u8::try_from(self.delimiter).unwrap_or(b'?');
+        frame[19] = self.fw_version_major;
+        frame[20] = self.fw_version_minor;
+        let (payload, checksum) = frame.split_at_mut(MODULE_LEN - 2);
+        checksum.copy_from_slice(&calculate_checksum(payload));
+        frame
+    }
+}
+
+/// Builds a [`Module`], such as to encode with [`Module::to_bytes`].
+#[derive(Debug, Clone)]
+pub struct ModuleBuilder(Module);
+
+impl ModuleBuilder {
+    setters!(Module {
+        name_and_type: ModuleName,
+        serial_number: u64,
+        delimiter: char,
+        fw_version_major: u8,
+        fw_version_minor: u8,
+    });
+
+    pub fn build(self) -> Module {
+        self.0
+    }
+}
+
+/// Every place in `buf` an `N` byte frame may start, with its offset: each
+/// frame header whose length field, if present, matches `N`.
+///
+/// A frame cut short by the end of `buf` is [`crate::Error::Truncated`].
+fn frames<const N: usize>(
+    buf: &[u8],
+) -> impl Iterator<Item = (usize, Result<&[u8; N], crate::Error>)> {
+    (0..buf.len())
+        .filter(move |&start| buf[start..].starts_with(&HEADER))
+        .filter(move |&start| match buf.get(start + 2..start + 4) {
+            // The length covers everything after the header and the length field.
+            Some(length) => usize::from(u16::from_be_bytes([length[0], length[1]])) + 4 == N,
+            None => true,
+        })
+        .map(move |start| {
+            let frame = &buf[start..];
+            let frame = frame.first_chunk().ok_or(crate::Error::Truncated {
+                expected: N,
+                actual: frame.len(),
+            });
+            (start, frame)
+        })
+}
+
+/// Return the first `N` byte frame in `buf`.
+fn locate<const N: usize>(buf: &[u8]) -> Result<&[u8; N], crate::Error> {
+    frames(buf)
+        .next()
+        .map_or(Err(crate::Error::InvalidHeader), |(_, frame)| frame)
+}
+
+/// Try each `N` byte frame in `buf` in turn until `parse` accepts one,
+/// returning the result and the offset of the frame's end.
+fn find_in<T, const N: usize>(
+    buf: &[u8],
+    parse: impl Fn(&[u8; N]) -> Result<T, crate::Error>,
+) -> Option<(T, usize)> {
+    frames(buf).find_map(|(start, frame)| {
+        let parsed = parse(frame.ok()?).ok()?;
+        Some((parsed, start + N))
+    })
+}
+
 impl Display for Module {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
             f,
             concat!(
@@ -292,10 +820,26 @@ impl Display for Module {
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
     use super::*;
     use crate::Error;
 
+    extern crate std;
+    use std::{string::ToString, vec::Vec};
+
+    /// A valid measurement frame read from an APC1-I, shared by the tests
+    /// throughout the crate.
+    pub(crate) const MEASUREMENT: [u8; 64] = [
+        66, 77, 0, 60, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 80, 0, 100, 0, 20, 0, 10, 0, 0, 0, 0,
+        0, 37, 1, 173, 0, 1, 0, 202, 2, 69, 0, 251, 1, 181, 0, 3, 101, 146, 0, 0, 0, 1, 0, 12, 19,
+        208, 0, 0, 147, 102, 1, 0, 35, 0, 8, 59,
+    ];
+
+    /// The module ID frame from the same device.
+    pub(crate) const MODULE: [u8; 23] = [
+        66, 77, 0, 19, 65, 80, 67, 49, 45, 73, 8, 110, 169, 135, 242, 77, 68, 134, 45, 0, 35, 6, 28,
+    ];
+
     #[test]
     fn try_from_valid_measurement() {
         let valid_measurement: &[u8; 64] = &[
@@ -339,6 +883,51 @@ mod test {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn try_from_measurement_device_error() {
+        // The valid measurement, with the fan stopped and a laser error.
+        let mut measurement = MEASUREMENT;
+        measurement[61] = 0x18;
+        measurement[63] = 83;
+        let Err(Error::Device(code)) = Measurement::try_from(&measurement) else {
+            panic!("expected a device error");
+        };
+        assert!(code.fan_stopped());
+        assert!(code.laser_error());
+        assert!(!code.fan_speed_low());
+        assert!(!code.voc_sensor_error());
+        assert_eq!(
+            code.faults().collect::<Vec<_>>(),
+            [DeviceFault::FanStopped, DeviceFault::Laser]
+        );
+        assert_eq!(code.to_string(), "fan stopped, laser error");
+
+        // Bit 7 is unused, so it isn't a fault.
+        measurement[61] = 0x80;
+        measurement[63] = 187;
+        assert!(Measurement::try_from(&measurement).is_ok());
+        let code = DeviceErrorCode::from_bits(0x80);
+        assert!(code.is_empty());
+        assert_eq!(code.faults().count(), 0);
+    }
+
+    #[test]
+    fn measurement_accessors() {
+        let mut measurement = Measurement::try_from(&MEASUREMENT).unwrap();
+        assert_eq!(measurement.temperature().celsius(), 20.2);
+        assert_eq!(measurement.humidity().percent(), 58.1);
+        assert_eq!(measurement.raw_temperature().celsius(), 25.1);
+        assert_eq!(measurement.um_0_3_particle_count().per_liter(), 3360);
+
+        measurement.pm2_5 = 1_000;
+        assert_eq!(
+            measurement.pm2_5_concentration(),
+            Some(MassConcentration::from_micrograms_per_cubic_meter(1_000))
+        );
+        measurement.pm2_5 = 1_001;
+        assert_eq!(measurement.pm2_5_concentration(), None);
+    }
+
     #[test]
     fn try_from_module_valid() {
         let valid_module: &[u8; 23] = &[
@@ -346,7 +935,7 @@ mod test {
             6, 28,
         ];
         let actual: Module = valid_module.try_into().unwrap();
-        assert_eq!(actual.name_and_type, "APC1-I");
+        assert_eq!(actual.name_and_type.as_str(), "APC1-I");
         assert_eq!(actual.serial_number, 607609401092424838_u64);
         assert_eq!(actual.delimiter, '-');
         assert_eq!(actual.fw_version_major, 0);
@@ -381,4 +970,121 @@ mod test {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn try_from_slice() {
+        let mut buf = Vec::from([0, 1, 2]);
+        buf.extend_from_slice(&MEASUREMENT);
+        buf.extend_from_slice(&[66, 77]);
+        let measurement = Measurement::try_from(buf.as_slice()).unwrap();
+        assert_eq!(measurement.eco2, 429);
+
+        // A header with the wrong length, such as a pair of payload bytes, is passed over.
+        let mut shifted = Vec::from(&MEASUREMENT[32..]);
+        shifted.extend_from_slice(&[66, 77, 1, 2]);
+        shifted.extend_from_slice(&MEASUREMENT);
+        assert_eq!(
+            Measurement::try_from(shifted.as_slice()),
+            Ok(Measurement::find_in(&shifted).unwrap().0)
+        );
+
+        assert_eq!(
+            Measurement::try_from(&buf[..40]),
+            Err(Error::Truncated {
+                expected: 64,
+                actual: 37
+            })
+        );
+        assert_eq!(
+            Measurement::try_from(&MODULE[..]),
+            Err(Error::InvalidHeader)
+        );
+        assert_eq!(
+            Measurement::try_from(&[0; 80][..]),
+            Err(Error::InvalidHeader)
+        );
+        assert_eq!(
+            Module::try_from(&MEASUREMENT[..]),
+            Err(Error::InvalidHeader)
+        );
+        assert_eq!(
+            Module::try_from(&MODULE[..]).unwrap().serial_number,
+            607609401092424838
+        );
+    }
+
+    #[test]
+    fn find_in() {
+        // Noise that looks like the start of a frame, then a module and a measurement.
+        let mut buf = Vec::from([66, 77, 0, 60, 1]);
+        buf.extend_from_slice(&MODULE);
+        buf.extend_from_slice(&MEASUREMENT);
+
+        let (measurement, consumed) = Measurement::find_in(&buf).unwrap();
+        assert_eq!(measurement.tvoc, 37);
+        assert_eq!(consumed, buf.len());
+        let (module, consumed) = Module::find_in(&buf).unwrap();
+        assert_eq!(module.fw_version_minor, 35);
+        assert_eq!(consumed, 5 + MODULE_LEN);
+
+        assert_eq!(Measurement::find_in(&buf[..buf.len() - 1]), None);
+        assert_eq!(Module::find_in(&[]), None);
+    }
+
+    #[test]
+    fn to_bytes_round_trip() {
+        let measurement = Measurement::try_from(&MEASUREMENT).unwrap();
+        assert_eq!(measurement.to_bytes(), MEASUREMENT);
+        let module = Module::try_from(&MODULE).unwrap();
+        assert_eq!(module.to_bytes(), MODULE);
+
+        // Every field is distinct, so one read from the wrong offset is caught.
+        let measurement = Measurement::builder()
+            .pm1_0(1)
+            .pm2_5(2)
+            .pm10(3)
+            .pm1_0_in_air(0x0104)
+            .pm2_5_in_air(0x0205)
+            .pm10_in_air(0x0306)
+            .um_0_3_particles(7)
+            .um_0_5_particles(8)
+            .um_1_particles(9)
+            .um_2_5_particles(10)
+            .um_5_particles(11)
+            .um_10_particles(12)
+            .tvoc(13)
+            .eco2(400)
+            .t_comp(15)
+            .rh_comp(16)
+            .t_raw(17)
+            .rh_raw(18)
+            .rs_0(0x0102_0304)
+            .rs_1(20)
+            .rs_2(21)
+            .rs_3(22)
+            .aqi(5)
+            .version(35)
+            .build();
+        assert_eq!(
+            Measurement::try_from(&measurement.to_bytes()),
+            Ok(measurement.clone())
+        );
+        let Err(Error::Device(code)) =
+            Measurement::try_from(&measurement.to_bytes_with_faults(DeviceErrorCode(0x08)))
+        else {
+            panic!("expected a device error");
+        };
+        assert!(code.fan_stopped());
+
+        // Padding and other bytes that aren't printable are kept as sent.
+        let module = Module::builder()
+            .name_and_type(ModuleName::from_bytes(*b"APC1 \0"))
+            .serial_number(42)
+            .delimiter('-')
+            .fw_version_minor(36)
+            .build();
+        assert_eq!(Module::try_from(&module.to_bytes()), Ok(module.clone()));
+        assert_eq!(module.to_bytes()[4..10], *b"APC1 \0");
+        assert_eq!(module.name_and_type.as_str(), "APC1 ?");
+    }
 }
@@ -0,0 +1,132 @@
+//! Typed values for the quantities the APC1 reports.
+//!
+//! The device encodes most values as integers in scaled units, such as tenths
+//! of a degree. These types carry the unit with the value so it can't be
+//! misinterpreted, and convert to the units callers typically want.
+use core::fmt::Display;
+
+/// A temperature, stored as the device reports it in units of 0.1°C.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Temperature(u16);
+
+impl Temperature {
+    pub const fn from_decidegrees_celsius(value: u16) -> Self {
+        Self(value)
+    }
+
+    /// The temperature in units of 0.1°C, as reported by the device.
+    pub const fn decidegrees_celsius(&self) -> u16 {
+        self.0
+    }
+
+    pub fn celsius(&self) -> f32 {
+        f32::from(self.0) / 10.0
+    }
+
+    pub fn fahrenheit(&self) -> f32 {
+        self.celsius() * 9.0 / 5.0 + 32.0
+    }
+}
+
+impl Display for Temperature {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}°C", self.celsius())
+    }
+}
+
+/// Relative humidity, stored as the device reports it in units of 0.1% RH.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RelativeHumidity(u16);
+
+impl RelativeHumidity {
+    pub const fn from_per_mille(value: u16) -> Self {
+        Self(value)
+    }
+
+    /// The relative humidity in units of 0.1%, as reported by the device.
+    pub const fn per_mille(&self) -> u16 {
+        self.0
+    }
+
+    pub fn percent(&self) -> f32 {
+        f32::from(self.0) / 10.0
+    }
+}
+
+impl Display for RelativeHumidity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}%", self.percent())
+    }
+}
+
+/// A particulate matter mass concentration in μg/m³.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MassConcentration(u16);
+
+impl MassConcentration {
+    pub const fn from_micrograms_per_cubic_meter(value: u16) -> Self {
+        Self(value)
+    }
+
+    pub const fn micrograms_per_cubic_meter(&self) -> u16 {
+        self.0
+    }
+}
+
+impl Display for MassConcentration {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}μg/m³", self.0)
+    }
+}
+
+/// A number of particles, stored as the device reports it per 0.1 liters of air.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ParticleCount(u16);
+
+impl ParticleCount {
+    pub const fn from_per_deciliter(value: u16) -> Self {
+        Self(value)
+    }
+
+    /// The number of particles in 0.1 liters of air, as reported by the device.
+    pub const fn per_deciliter(&self) -> u16 {
+        self.0
+    }
+
+    pub const fn per_liter(&self) -> u32 {
+        self.0 as u32 * 10
+    }
+}
+
+impl Display for ParticleCount {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} per 0.1L", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn temperature_conversions() {
+        let temperature = Temperature::from_decidegrees_celsius(202);
+        assert_eq!(temperature.celsius(), 20.2);
+        assert_eq!(temperature.fahrenheit(), 68.36);
+        assert_eq!(Temperature::from_decidegrees_celsius(0).fahrenheit(), 32.0);
+        assert_eq!(
+            Temperature::from_decidegrees_celsius(500).fahrenheit(),
+            122.0
+        );
+    }
+
+    #[test]
+    fn humidity_conversions() {
+        assert_eq!(RelativeHumidity::from_per_mille(581).percent(), 58.1);
+    }
+
+    #[test]
+    fn particle_count_conversions() {
+        assert_eq!(ParticleCount::from_per_deciliter(65535).per_liter(), 655350);
+    }
+}
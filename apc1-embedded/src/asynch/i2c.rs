@@ -0,0 +1,225 @@
+//! Async driver for the I2C variant of the APC1.
+use core::marker::PhantomData;
+
+use apc1_core::{
+    i2c::{self, Command},
+    Measurement, Module,
+};
+use embedded_hal_async::{delay::DelayNs, i2c::I2c};
+
+use crate::{check_fresh, is_fatal, Active, Error, Idle, RetryPolicy, Transition, READY_POLL_MS};
+
+/// An APC1-I connected to an async I2C bus.
+///
+/// The `MODE` parameter tracks whether the fan is running, so measurements
+/// can only be read from an [`Active`] sensor.
+pub struct Apc1Sensor<I2C, MODE = Active> {
+    i2c: I2C,
+    _mode: PhantomData<MODE>,
+}
+
+impl<I2C: I2c> Apc1Sensor<I2C, Active> {
+    /// Create a driver for the sensor at [`i2c::DEVICE_ADDR`] on the given bus.
+    ///
+    /// This assumes the sensor is in its power-on state. If that's not
+    /// certain, for example after the host restarts without power cycling the
+    /// sensor, call [`Apc1Sensor::reset`].
+    pub fn new(i2c: I2C) -> Self {
+        Self {
+            i2c,
+            _mode: PhantomData,
+        }
+    }
+
+    /// Read the device's most recent measurement.
+    ///
+    /// The device updates its measurement once a second.
+    pub async fn measurement(&mut self) -> Result<Measurement, Error<I2C::Error>> {
+        let mut buf: [u8; 64] = [0; 64];
+        self.i2c
+            .read(i2c::DEVICE_ADDR, &mut buf)
+            .await
+            .map_err(Error::Bus)?;
+        Ok(Measurement::try_from(&buf)?)
+    }
+
+    /// Read a measurement, retrying transient failures as `policy` allows.
+    ///
+    /// The error from the final attempt is returned if every attempt fails.
+    pub async fn measurement_with_retry<D: DelayNs>(
+        &mut self,
+        policy: &RetryPolicy,
+        delay: &mut D,
+    ) -> Result<Measurement, Error<I2C::Error>> {
+        let mut attempts = 1;
+        loop {
+            match self.measurement().await {
+                Err(e) if e.is_transient() && attempts < policy.max_attempts => {
+                    attempts += 1;
+                    delay.delay_ms(policy.delay_ms).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Wait for the device to send a new, valid measurement, as it does once
+    /// it has finished starting up after power-on or [`Apc1Sensor::reset`].
+    ///
+    /// The device is checked every [`READY_POLL_MS`] milliseconds. Until it is
+    /// ready it may send corrupt frames, report faults, or repeat a stale
+    /// measurement, so every error is retried unless the bus itself failed.
+    /// Since the first valid measurement may be stale, the device is only
+    /// ready once a different one is read, which takes about a second. If it
+    /// isn't ready within `timeout_ms`, the last error is returned.
+    pub async fn wait_ready<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        timeout_ms: u32,
+    ) -> Result<Measurement, Error<I2C::Error>> {
+        let mut first = None;
+        let mut waited = 0;
+        loop {
+            match check_fresh(&mut first, self.measurement().await) {
+                Err(e) if !is_fatal(&e) && waited + READY_POLL_MS <= timeout_ms => {
+                    waited += READY_POLL_MS;
+                    delay.delay_ms(READY_POLL_MS).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Power down the fan.
+    pub async fn into_idle(mut self) -> Transition<Apc1Sensor<I2C, Idle>, Self, I2C::Error> {
+        match self.send(Command::SetIdleMode).await {
+            Ok(()) => Ok(self.into_mode()),
+            Err(e) => Err((self, e)),
+        }
+    }
+}
+
+impl<I2C: I2c> Apc1Sensor<I2C, Idle> {
+    /// Power on the fan.
+    ///
+    /// Measurements take several seconds to stabilize after the fan starts.
+    pub async fn into_active(mut self) -> Transition<Apc1Sensor<I2C, Active>, Self, I2C::Error> {
+        match self.send(Command::SetActiveMode).await {
+            Ok(()) => Ok(self.into_mode()),
+            Err(e) => Err((self, e)),
+        }
+    }
+}
+
+impl<I2C: I2c, MODE> Apc1Sensor<I2C, MODE> {
+    /// Request the device's module type, ID, and firmware version.
+    pub async fn module(&mut self) -> Result<Module, Error<I2C::Error>> {
+        self.send(Command::ReadModuleId).await?;
+        let mut buf: [u8; 23] = [0; 23];
+        for (register, byte) in (i2c::RESPONSE_BASE_ADDR..).zip(buf.iter_mut()) {
+            self.i2c
+                .write_read(i2c::DEVICE_ADDR, &[register], core::slice::from_mut(byte))
+                .await
+                .map_err(Error::Bus)?;
+        }
+        Ok(Module::try_from(&buf)?)
+    }
+
+    /// Restart the device, returning it to its power-on state.
+    ///
+    /// The device does not respond for about a second while it restarts;
+    /// use [`Apc1Sensor::wait_ready`] to wait for it.
+    pub async fn reset(mut self) -> Transition<Apc1Sensor<I2C, Active>, Self, I2C::Error> {
+        match self.send(Command::Reset).await {
+            Ok(()) => Ok(self.into_mode()),
+            Err(e) => Err((self, e)),
+        }
+    }
+
+    /// Release the underlying I2C bus.
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Write a command to the command registers, one byte at a time.
+    async fn send(&mut self, command: Command) -> Result<(), Error<I2C::Error>> {
+        for (register, byte) in (i2c::COMMAND_BASE_ADDR..).zip(command.to_bytes()) {
+            self.i2c
+                .write(i2c::DEVICE_ADDR, &[register, byte])
+                .await
+                .map_err(Error::Bus)?;
+        }
+        Ok(())
+    }
+
+    fn into_mode<NEXT>(self) -> Apc1Sensor<I2C, NEXT> {
+        Apc1Sensor {
+            i2c: self.i2c,
+            _mode: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+    use std::vec;
+
+    use embedded_hal_mock::eh1::{
+        delay::{CheckedDelay, Transaction as DelayTransaction},
+        i2c::{Mock, Transaction},
+    };
+
+    use super::*;
+    use crate::asynch::test::block_on;
+    use crate::i2c::test::{
+        command, corrupt_measurement, module_transactions, next_measurement, MEASUREMENT,
+    };
+
+    #[test]
+    fn measurement() {
+        let bus = Mock::new(&[Transaction::read(i2c::DEVICE_ADDR, MEASUREMENT.to_vec())]);
+
+        let mut sensor = Apc1Sensor::new(bus);
+        let measurement = block_on(sensor.measurement()).unwrap();
+        assert_eq!(measurement.aqi, 1);
+
+        sensor.release().done();
+    }
+
+    #[test]
+    fn module_and_mode_transitions() {
+        let mut transactions = command(Command::SetIdleMode);
+        transactions.extend(module_transactions());
+        transactions.extend(command(Command::SetActiveMode));
+        let bus = Mock::new(&transactions);
+
+        let sensor = Apc1Sensor::new(bus);
+        let mut sensor = block_on(sensor.into_idle()).map_err(|(_, e)| e).unwrap();
+        let module = block_on(sensor.module()).unwrap();
+        assert_eq!(module.name_and_type.as_str(), "APC1-I");
+        let sensor = block_on(sensor.into_active()).map_err(|(_, e)| e).unwrap();
+
+        sensor.release().done();
+    }
+
+    #[test]
+    fn wait_ready() {
+        let bus = Mock::new(&[
+            Transaction::read(i2c::DEVICE_ADDR, vec![0; 64])
+                .with_error(embedded_hal::i2c::ErrorKind::Bus),
+            Transaction::read(i2c::DEVICE_ADDR, corrupt_measurement()),
+            Transaction::read(i2c::DEVICE_ADDR, MEASUREMENT.to_vec()),
+            Transaction::read(i2c::DEVICE_ADDR, next_measurement()),
+        ]);
+        let mut delay =
+            CheckedDelay::new(&vec![DelayTransaction::async_delay_ms(READY_POLL_MS); 3]);
+
+        let mut sensor = Apc1Sensor::new(bus);
+        let measurement = block_on(sensor.wait_ready(&mut delay, 1_000)).unwrap();
+        assert_eq!(measurement.aqi, 2);
+
+        sensor.release().done();
+        delay.done();
+    }
+}
@@ -0,0 +1,33 @@
+//! Async versions of the drivers, for use with executors such as Embassy.
+//!
+//! These mirror [`crate::Apc1Sensor`] and [`crate::Apc1UartSensor`], but are
+//! built on [`embedded_hal_async`] and [`embedded_io_async`] so reading from
+//! the sensor never blocks the executor.
+mod i2c;
+mod uart;
+
+pub use i2c::Apc1Sensor;
+pub use uart::Apc1UartSensor;
+
+#[cfg(test)]
+mod test {
+    use core::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    /// Run a future which never waits to completion.
+    ///
+    /// The mocks used in the tests are always ready, so there is no need for
+    /// a real executor.
+    pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Async driver for the UART variant of the APC1.
+use apc1_core::{uart::Command, Frame, FrameParser, Measurement, Module};
+use embedded_io_async::{Read, Write};
+
+use crate::{Error, MeasurementMode};
+
+/// An APC1-U connected to an async serial port.
+///
+/// See [`crate::Apc1UartSensor`] for details on each method.
+pub struct Apc1UartSensor<U> {
+    uart: U,
+    parser: FrameParser,
+}
+
+impl<U: Read + Write> Apc1UartSensor<U> {
+    /// Create a driver for the sensor attached to the given serial port.
+    pub fn new(uart: U) -> Self {
+        Self {
+            uart,
+            parser: FrameParser::new(),
+        }
+    }
+
+    /// Change how the device reports measurements.
+    pub async fn set_measurement_mode(
+        &mut self,
+        mode: MeasurementMode,
+    ) -> Result<(), Error<U::Error>> {
+        let command = match mode {
+            MeasurementMode::Active => Command::SetActiveMeasurement,
+            MeasurementMode::Passive => Command::SetPassiveMeasurement,
+        };
+        self.send(command).await
+    }
+
+    /// Ask the device for a measurement and wait for it.
+    pub async fn request_measurement(&mut self) -> Result<Measurement, Error<U::Error>> {
+        self.parser.reset();
+        self.send(Command::RequestMeasurement).await?;
+        self.read_measurement().await
+    }
+
+    /// Wait for the next measurement the device sends.
+    pub async fn read_measurement(&mut self) -> Result<Measurement, Error<U::Error>> {
+        self.read_frame(|frame| match frame {
+            Frame::Measurement(measurement) => Some(measurement),
+            Frame::Module(_) => None,
+        })
+        .await
+    }
+
+    /// Request the device's module type, ID, and firmware version.
+    pub async fn module(&mut self) -> Result<Module, Error<U::Error>> {
+        self.parser.reset();
+        self.send(Command::ReadModuleId).await?;
+        self.read_frame(|frame| match frame {
+            Frame::Module(module) => Some(module),
+            Frame::Measurement(_) => None,
+        })
+        .await
+    }
+
+    /// Release the underlying serial port.
+    pub fn release(self) -> U {
+        self.uart
+    }
+
+    async fn send(&mut self, command: Command) -> Result<(), Error<U::Error>> {
+        self.uart
+            .write_all(&command.to_bytes())
+            .await
+            .map_err(Error::Bus)?;
+        self.uart.flush().await.map_err(Error::Bus)
+    }
+
+    async fn read_frame<T>(
+        &mut self,
+        select: impl Fn(Frame) -> Option<T>,
+    ) -> Result<T, Error<U::Error>> {
+        let mut byte = [0_u8; 1];
+        let mut read = 0;
+        loop {
+            for frame in self.parser.feed(&byte[..read]) {
+                if let Some(value) = select(frame?) {
+                    return Ok(value);
+                }
+            }
+            read = self.uart.read(&mut byte).await.map_err(Error::Bus)?;
+            if read == 0 {
+                return Err(Error::UnexpectedEof);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::asynch::test::block_on;
+    use crate::i2c::test::MEASUREMENT;
+    use crate::uart::test::FakeUart;
+
+    impl Read for FakeUart {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            Ok(self.read_rx(buf))
+        }
+    }
+
+    impl Write for FakeUart {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.tx.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn request_measurement() {
+        let mut uart = FakeUart::default();
+        uart.rx
+            .extend([0x42, 0x42, 0x4D, 0x00, 0x04, 0xE1, 0x00, 0x01, 0x74]);
+        uart.rx.extend(MEASUREMENT);
+        let mut sensor = Apc1UartSensor::new(uart);
+
+        let measurement = block_on(sensor.request_measurement()).unwrap();
+
+        assert_eq!(measurement.aqi, 1);
+        assert_eq!(
+            sensor.release().tx,
+            Command::RequestMeasurement.to_bytes().as_slice()
+        );
+    }
+}
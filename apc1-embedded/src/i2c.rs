@@ -0,0 +1,392 @@
+//! Driver for the I2C variant of the APC1.
+use core::marker::PhantomData;
+
+use apc1_core::{
+    i2c::{self, Command},
+    Measurement, Module,
+};
+use embedded_hal::{delay::DelayNs, i2c::I2c};
+
+use crate::{check_fresh, is_fatal, Error, RetryPolicy, READY_POLL_MS};
+
+/// The sensor's fan is running and it is taking measurements. This is the
+/// state the device powers on in.
+pub struct Active;
+
+/// The sensor's fan is powered down, reducing its current draw from ~75mA to
+/// ~9mA. Measurements are not available in this state.
+pub struct Idle;
+
+/// The result of changing the sensor's state.
+///
+/// If the command can't be sent, the sensor is returned in its original state
+/// along with the error so the bus is not lost.
+pub type Transition<Next, Current, E> = Result<Next, (Current, Error<E>)>;
+
+/// An APC1-I connected to an I2C bus.
+///
+/// The `MODE` parameter tracks whether the fan is running, so measurements
+/// can only be read from an [`Active`] sensor.
+pub struct Apc1Sensor<I2C, MODE = Active> {
+    i2c: I2C,
+    _mode: PhantomData<MODE>,
+}
+
+impl<I2C: I2c> Apc1Sensor<I2C, Active> {
+    /// Create a driver for the sensor at [`i2c::DEVICE_ADDR`] on the given bus.
+    ///
+    /// This assumes the sensor is in its power-on state. If that's not
+    /// certain, for example after the host restarts without power cycling the
+    /// sensor, call [`Apc1Sensor::reset`].
+    pub fn new(i2c: I2C) -> Self {
+        Self {
+            i2c,
+            _mode: PhantomData,
+        }
+    }
+
+    /// Read the device's most recent measurement.
+    ///
+    /// The device updates its measurement once a second.
+    pub fn measurement(&mut self) -> Result<Measurement, Error<I2C::Error>> {
+        let mut buf: [u8; 64] = [0; 64];
+        self.i2c
+            .read(i2c::DEVICE_ADDR, &mut buf)
+            .map_err(Error::Bus)?;
+        Ok(Measurement::try_from(&buf)?)
+    }
+
+    /// Read a measurement, retrying transient failures as `policy` allows.
+    ///
+    /// The error from the final attempt is returned if every attempt fails.
+    pub fn measurement_with_retry<D: DelayNs>(
+        &mut self,
+        policy: &RetryPolicy,
+        delay: &mut D,
+    ) -> Result<Measurement, Error<I2C::Error>> {
+        let mut attempts = 1;
+        loop {
+            match self.measurement() {
+                Err(e) if e.is_transient() && attempts < policy.max_attempts => {
+                    attempts += 1;
+                    delay.delay_ms(policy.delay_ms);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Wait for the device to send a new, valid measurement, as it does once
+    /// it has finished starting up after power-on or [`Apc1Sensor::reset`].
+    ///
+    /// The device is checked every [`READY_POLL_MS`] milliseconds. Until it is
+    /// ready it may send corrupt frames, report faults, or repeat a stale
+    /// measurement, so every error is retried unless the bus itself failed.
+    /// Since the first valid measurement may be stale, the device is only
+    /// ready once a different one is read, which takes about a second. If it
+    /// isn't ready within `timeout_ms`, the last error is returned.
+    pub fn wait_ready<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        timeout_ms: u32,
+    ) -> Result<Measurement, Error<I2C::Error>> {
+        let mut first = None;
+        let mut waited = 0;
+        loop {
+            match check_fresh(&mut first, self.measurement()) {
+                Err(e) if !is_fatal(&e) && waited + READY_POLL_MS <= timeout_ms => {
+                    waited += READY_POLL_MS;
+                    delay.delay_ms(READY_POLL_MS);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Power down the fan.
+    pub fn into_idle(mut self) -> Transition<Apc1Sensor<I2C, Idle>, Self, I2C::Error> {
+        match self.send(Command::SetIdleMode) {
+            Ok(()) => Ok(self.into_mode()),
+            Err(e) => Err((self, e)),
+        }
+    }
+}
+
+impl<I2C: I2c> Apc1Sensor<I2C, Idle> {
+    /// Power on the fan.
+    ///
+    /// Measurements take several seconds to stabilize after the fan starts.
+    pub fn into_active(mut self) -> Transition<Apc1Sensor<I2C, Active>, Self, I2C::Error> {
+        match self.send(Command::SetActiveMode) {
+            Ok(()) => Ok(self.into_mode()),
+            Err(e) => Err((self, e)),
+        }
+    }
+}
+
+impl<I2C: I2c, MODE> Apc1Sensor<I2C, MODE> {
+    /// Request the device's module type, ID, and firmware version.
+    pub fn module(&mut self) -> Result<Module, Error<I2C::Error>> {
+        self.send(Command::ReadModuleId)?;
+        let mut buf: [u8; 23] = [0; 23];
+        for (register, byte) in (i2c::RESPONSE_BASE_ADDR..).zip(buf.iter_mut()) {
+            self.i2c
+                .write_read(i2c::DEVICE_ADDR, &[register], core::slice::from_mut(byte))
+                .map_err(Error::Bus)?;
+        }
+        Ok(Module::try_from(&buf)?)
+    }
+
+    /// Restart the device, returning it to its power-on state.
+    ///
+    /// The device does not respond for about a second while it restarts;
+    /// use [`Apc1Sensor::wait_ready`] to wait for it.
+    pub fn reset(mut self) -> Transition<Apc1Sensor<I2C, Active>, Self, I2C::Error> {
+        match self.send(Command::Reset) {
+            Ok(()) => Ok(self.into_mode()),
+            Err(e) => Err((self, e)),
+        }
+    }
+
+    /// Release the underlying I2C bus.
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Write a command to the command registers, one byte at a time.
+    fn send(&mut self, command: Command) -> Result<(), Error<I2C::Error>> {
+        for (register, byte) in (i2c::COMMAND_BASE_ADDR..).zip(command.to_bytes()) {
+            self.i2c
+                .write(i2c::DEVICE_ADDR, &[register, byte])
+                .map_err(Error::Bus)?;
+        }
+        Ok(())
+    }
+
+    fn into_mode<NEXT>(self) -> Apc1Sensor<I2C, NEXT> {
+        Apc1Sensor {
+            i2c: self.i2c,
+            _mode: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    extern crate std;
+    use std::{vec, vec::Vec};
+
+    use embedded_hal_mock::eh1::{
+        delay::{CheckedDelay, Transaction as DelayTransaction},
+        i2c::{Mock, Transaction},
+    };
+
+    use super::*;
+
+    /// A module ID frame read from an APC1-I, shared by the tests throughout
+    /// the crate.
+    pub(crate) const MODULE: [u8; 23] = [
+        66, 77, 0, 19, 65, 80, 67, 49, 45, 73, 8, 110, 169, 135, 242, 77, 68, 134, 45, 0, 35, 6, 28,
+    ];
+
+    /// A valid measurement frame from the same device.
+    pub(crate) const MEASUREMENT: [u8; 64] = [
+        66, 77, 0, 60, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 80, 0, 100, 0, 20, 0, 10, 0, 0, 0, 0,
+        0, 37, 1, 173, 0, 1, 0, 202, 2, 69, 0, 251, 1, 181, 0, 3, 101, 146, 0, 0, 0, 1, 0, 12, 19,
+        208, 0, 0, 147, 102, 1, 0, 35, 0, 8, 59,
+    ];
+
+    /// [`MEASUREMENT`] with the checksum off by one.
+    pub(crate) fn corrupt_measurement() -> Vec<u8> {
+        let mut frame = MEASUREMENT.to_vec();
+        frame[63] += 1;
+        frame
+    }
+
+    /// A valid measurement that differs from [`MEASUREMENT`].
+    pub(crate) fn next_measurement() -> Vec<u8> {
+        let mut measurement = Measurement::try_from(&MEASUREMENT).unwrap();
+        measurement.aqi = 2;
+        measurement.to_bytes().to_vec()
+    }
+
+    /// The transactions expected when `command` is sent.
+    pub(crate) fn command(command: Command) -> Vec<Transaction> {
+        (i2c::COMMAND_BASE_ADDR..)
+            .zip(command.to_bytes())
+            .map(|(register, byte)| Transaction::write(i2c::DEVICE_ADDR, vec![register, byte]))
+            .collect()
+    }
+
+    /// The transactions expected when reading the module ID.
+    pub(crate) fn module_transactions() -> Vec<Transaction> {
+        let mut transactions = command(Command::ReadModuleId);
+        transactions.extend(
+            (i2c::RESPONSE_BASE_ADDR..)
+                .zip(MODULE)
+                .map(|(register, byte)| {
+                    Transaction::write_read(i2c::DEVICE_ADDR, vec![register], vec![byte])
+                }),
+        );
+        transactions
+    }
+
+    #[test]
+    fn measurement() {
+        let bus = Mock::new(&[Transaction::read(i2c::DEVICE_ADDR, MEASUREMENT.to_vec())]);
+
+        let mut sensor = Apc1Sensor::new(bus);
+        let measurement = sensor.measurement().unwrap();
+        assert_eq!(measurement.aqi, 1);
+
+        sensor.release().done();
+    }
+
+    #[test]
+    fn module() {
+        let bus = Mock::new(&module_transactions());
+
+        let mut sensor = Apc1Sensor::new(bus);
+        let module = sensor.module().unwrap();
+        assert_eq!(module.name_and_type.as_str(), "APC1-I");
+
+        sensor.release().done();
+    }
+
+    #[test]
+    fn mode_transitions() {
+        let mut transactions = command(Command::SetIdleMode);
+        transactions.extend(command(Command::SetActiveMode));
+        transactions.extend(command(Command::SetIdleMode));
+        transactions.extend(command(Command::Reset));
+        let bus = Mock::new(&transactions);
+
+        let sensor = Apc1Sensor::new(bus);
+        let sensor = sensor.into_idle().map_err(|(_, e)| e).unwrap();
+        let sensor = sensor.into_active().map_err(|(_, e)| e).unwrap();
+        let sensor = sensor.into_idle().map_err(|(_, e)| e).unwrap();
+        let sensor: Apc1Sensor<_, Active> = sensor.reset().map_err(|(_, e)| e).unwrap();
+
+        sensor.release().done();
+    }
+
+    #[test]
+    fn failed_transition_returns_sensor() {
+        let mut transactions = command(Command::SetIdleMode);
+        transactions.truncate(1);
+        transactions.push(
+            Transaction::write(i2c::DEVICE_ADDR, vec![0x41, 0x4D])
+                .with_error(embedded_hal::i2c::ErrorKind::Bus),
+        );
+        let bus = Mock::new(&transactions);
+
+        let sensor = Apc1Sensor::new(bus);
+        let Err((sensor, error)) = sensor.into_idle() else {
+            panic!("transition should have failed");
+        };
+        assert_eq!(error, Error::Bus(embedded_hal::i2c::ErrorKind::Bus));
+
+        sensor.release().done();
+    }
+
+    #[test]
+    fn measurement_with_retry() {
+        let mut faulty = MEASUREMENT.to_vec();
+        faulty[61] = 0x08;
+        faulty[63] += 0x08;
+        let bus = Mock::new(&[
+            Transaction::read(i2c::DEVICE_ADDR, vec![0; 64])
+                .with_error(embedded_hal::i2c::ErrorKind::Bus),
+            Transaction::read(i2c::DEVICE_ADDR, corrupt_measurement()),
+            Transaction::read(i2c::DEVICE_ADDR, MEASUREMENT.to_vec()),
+            // Device faults aren't retried.
+            Transaction::read(i2c::DEVICE_ADDR, faulty),
+            // Nor are transient errors once the attempts run out.
+            Transaction::read(i2c::DEVICE_ADDR, corrupt_measurement()),
+            Transaction::read(i2c::DEVICE_ADDR, corrupt_measurement()),
+        ]);
+        let mut delay = CheckedDelay::new(&[
+            DelayTransaction::delay_ms(1_100),
+            DelayTransaction::delay_ms(1_100),
+            DelayTransaction::delay_ms(10),
+        ]);
+
+        let mut sensor = Apc1Sensor::new(bus);
+        let policy = RetryPolicy::default();
+        let measurement = sensor.measurement_with_retry(&policy, &mut delay).unwrap();
+        assert_eq!(measurement.aqi, 1);
+        assert!(matches!(
+            sensor.measurement_with_retry(&policy, &mut delay),
+            Err(Error::Response(apc1_core::Error::Device(_)))
+        ));
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            delay_ms: 10,
+        };
+        assert!(matches!(
+            sensor.measurement_with_retry(&policy, &mut delay),
+            Err(Error::Response(apc1_core::Error::Checksum { .. }))
+        ));
+
+        sensor.release().done();
+        delay.done();
+    }
+
+    #[test]
+    fn wait_ready() {
+        let mut faulty = MEASUREMENT.to_vec();
+        faulty[61] = 0x08;
+        faulty[63] += 0x08;
+        let bus = Mock::new(&[
+            Transaction::read(i2c::DEVICE_ADDR, corrupt_measurement()),
+            Transaction::read(i2c::DEVICE_ADDR, corrupt_measurement()),
+            Transaction::read(i2c::DEVICE_ADDR, corrupt_measurement()),
+            // Faults and stale measurements are waited out.
+            Transaction::read(i2c::DEVICE_ADDR, faulty),
+            Transaction::read(i2c::DEVICE_ADDR, MEASUREMENT.to_vec()),
+            Transaction::read(i2c::DEVICE_ADDR, MEASUREMENT.to_vec()),
+            Transaction::read(i2c::DEVICE_ADDR, next_measurement()),
+            // The bus failing isn't.
+            Transaction::read(i2c::DEVICE_ADDR, vec![0; 64])
+                .with_error(embedded_hal::i2c::ErrorKind::Other),
+        ]);
+        let mut delay = CheckedDelay::new(&[
+            DelayTransaction::delay_ms(READY_POLL_MS),
+            DelayTransaction::delay_ms(READY_POLL_MS),
+        ]);
+
+        // The device isn't ready in time.
+        let mut sensor = Apc1Sensor::new(bus);
+        assert!(sensor.wait_ready(&mut delay, 250).is_err());
+        delay.done();
+
+        let mut delay = CheckedDelay::new(&vec![DelayTransaction::delay_ms(READY_POLL_MS); 3]);
+        let measurement = sensor.wait_ready(&mut delay, 1_000).unwrap();
+        assert_eq!(measurement.aqi, 2);
+        delay.done();
+
+        let mut delay = CheckedDelay::new(&[]);
+        assert_eq!(
+            sensor.wait_ready(&mut delay, 1_000),
+            Err(Error::Bus(embedded_hal::i2c::ErrorKind::Other))
+        );
+
+        sensor.release().done();
+        delay.done();
+    }
+
+    #[test]
+    fn wait_ready_stale() {
+        let bus = Mock::new(&[
+            Transaction::read(i2c::DEVICE_ADDR, MEASUREMENT.to_vec()),
+            Transaction::read(i2c::DEVICE_ADDR, MEASUREMENT.to_vec()),
+        ]);
+        let mut delay = CheckedDelay::new(&[DelayTransaction::delay_ms(READY_POLL_MS)]);
+
+        let mut sensor = Apc1Sensor::new(bus);
+        assert_eq!(sensor.wait_ready(&mut delay, 100), Err(Error::Stale));
+
+        sensor.release().done();
+        delay.done();
+    }
+}
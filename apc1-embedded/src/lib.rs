@@ -0,0 +1,118 @@
+//! Drivers for the APC1 built on the [`embedded_hal`] and [`embedded_io`] traits.
+//!
+//! [`Apc1Sensor`] drives the I2C variant (APC1-I) and [`Apc1UartSensor`]
+//! drives the UART variant (APC1-U). With the `async` feature enabled, the
+//! [`asynch`] module provides the same drivers for async executors such as
+//! Embassy.
+//!
+//! [`Apc1Sensor`] tracks whether the sensor's fan is powered with its `MODE`
+//! type parameter. Battery-powered devices can put the sensor in the
+//! [`Idle`] state between readings, and the compiler ensures measurements
+//! are only read while it is [`Active`].
+//!
+//! For a while after powering on or being reset, the sensor sends corrupt or
+//! stale frames, reports faults, or doesn't respond at all.
+//! [`Apc1Sensor::wait_ready`] waits for it to send a new, valid measurement,
+//! and [`Apc1Sensor::measurement_with_retry`]
+//! retries failed reads according to a [`RetryPolicy`].
+#![no_std]
+
+#[cfg(feature = "async")]
+pub mod asynch;
+mod i2c;
+mod uart;
+
+pub use i2c::{Active, Apc1Sensor, Idle, Transition};
+pub use uart::{Apc1UartSensor, MeasurementMode};
+
+use apc1_core::Measurement;
+use embedded_hal::i2c::ErrorKind;
+
+/// How often [`Apc1Sensor::wait_ready`] checks for a valid measurement, in
+/// milliseconds.
+pub const READY_POLL_MS: u32 = 100;
+
+/// How to retry reading a measurement that failed.
+///
+/// Only errors that may clear up by themselves are retried; see
+/// [`Error::is_transient`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The most reads to attempt, including the first. At least one read is
+    /// always attempted.
+    pub max_attempts: u32,
+    /// How long to wait after a failed read, in milliseconds.
+    pub delay_ms: u32,
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, a little over a second apart so each sees a new
+    /// measurement.
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            delay_ms: 1_100,
+        }
+    }
+}
+
+/// Errors returned by the drivers.
+///
+/// `E` is the error type of the underlying I2C bus or serial port.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error<E> {
+    /// The I2C bus or serial port reported an error.
+    #[error("Bus error: {0:?}")]
+    Bus(E),
+    /// The serial port reached end-of-file part way through a response.
+    #[error("Serial port closed before a complete response was read")]
+    UnexpectedEof,
+    /// The device sent an invalid response or reported a fault.
+    #[error(transparent)]
+    Response(#[from] apc1_core::Error),
+    /// The device kept sending the same measurement rather than taking new
+    /// ones, as it does for a while after starting.
+    #[error("The device repeated a stale measurement")]
+    Stale,
+}
+
+impl<E> Error<E> {
+    /// Whether trying again may succeed: the bus failed, which it does while
+    /// the device restarts, or the response was corrupt. Faults reported by
+    /// the device aren't transient.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Bus(_) => true,
+            Error::UnexpectedEof => false,
+            Error::Response(apc1_core::Error::Device(_)) => false,
+            Error::Response(_) => true,
+            Error::Stale => true,
+        }
+    }
+}
+
+/// Whether [`Apc1Sensor::wait_ready`] should give up rather than keep waiting.
+///
+/// A starting device may not acknowledge its address, send corrupt or stale
+/// frames, or report faults until its fan spins up, so only bus errors the
+/// HAL couldn't classify, such as the bus itself going away, are fatal.
+pub(crate) fn is_fatal<E: embedded_hal::i2c::Error>(error: &Error<E>) -> bool {
+    matches!(error, Error::Bus(e) if e.kind() == ErrorKind::Other)
+}
+
+/// Reject a measurement [`Apc1Sensor::wait_ready`] read unless it differs
+/// from the first it saw, which shows the device is taking new measurements.
+pub(crate) fn check_fresh<E>(
+    first: &mut Option<Measurement>,
+    result: Result<Measurement, Error<E>>,
+) -> Result<Measurement, Error<E>> {
+    let measurement = result?;
+    match first {
+        None => {
+            *first = Some(measurement);
+            Err(Error::Stale)
+        }
+        Some(first) if *first == measurement => Err(Error::Stale),
+        Some(_) => Ok(measurement),
+    }
+}
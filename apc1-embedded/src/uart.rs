@@ -0,0 +1,231 @@
+//! Driver for the UART variant of the APC1.
+//!
+//! The serial port must be configured as described in [`apc1_core::uart`]:
+//! 9,600 baud, 8 data bits, no parity, and 1 stop bit.
+use apc1_core::{uart::Command, Frame, FrameParser, Measurement, Module};
+use embedded_io::{Read, Write};
+
+use crate::Error;
+
+/// How the device reports measurements over UART.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeasurementMode {
+    /// The device sends a measurement every second without being asked.
+    Active,
+    /// The device only sends a measurement when requested. This is the
+    /// default mode the device starts in.
+    Passive,
+}
+
+/// An APC1-U connected to a serial port.
+///
+/// Responses are located in the byte stream with a [`FrameParser`], which is
+/// read a byte at a time so nothing after the response is consumed.
+pub struct Apc1UartSensor<U> {
+    uart: U,
+    parser: FrameParser,
+}
+
+impl<U: Read + Write> Apc1UartSensor<U> {
+    /// Create a driver for the sensor attached to the given serial port.
+    pub fn new(uart: U) -> Self {
+        Self {
+            uart,
+            parser: FrameParser::new(),
+        }
+    }
+
+    /// Change how the device reports measurements.
+    pub fn set_measurement_mode(&mut self, mode: MeasurementMode) -> Result<(), Error<U::Error>> {
+        let command = match mode {
+            MeasurementMode::Active => Command::SetActiveMeasurement,
+            MeasurementMode::Passive => Command::SetPassiveMeasurement,
+        };
+        self.send(command)
+    }
+
+    /// Ask the device for a measurement and wait for it.
+    ///
+    /// This is intended for [`MeasurementMode::Passive`].
+    pub fn request_measurement(&mut self) -> Result<Measurement, Error<U::Error>> {
+        self.parser.reset();
+        self.send(Command::RequestMeasurement)?;
+        self.read_measurement()
+    }
+
+    /// Wait for the next measurement the device sends.
+    ///
+    /// This is intended for [`MeasurementMode::Active`], where the device
+    /// sends a measurement every second.
+    pub fn read_measurement(&mut self) -> Result<Measurement, Error<U::Error>> {
+        self.read_frame(|frame| match frame {
+            Frame::Measurement(measurement) => Some(measurement),
+            Frame::Module(_) => None,
+        })
+    }
+
+    /// Request the device's module type, ID, and firmware version.
+    pub fn module(&mut self) -> Result<Module, Error<U::Error>> {
+        self.parser.reset();
+        self.send(Command::ReadModuleId)?;
+        self.read_frame(|frame| match frame {
+            Frame::Module(module) => Some(module),
+            Frame::Measurement(_) => None,
+        })
+    }
+
+    /// Release the underlying serial port.
+    pub fn release(self) -> U {
+        self.uart
+    }
+
+    fn send(&mut self, command: Command) -> Result<(), Error<U::Error>> {
+        self.uart
+            .write_all(&command.to_bytes())
+            .map_err(Error::Bus)?;
+        self.uart.flush().map_err(Error::Bus)
+    }
+
+    /// Read frames from the device until `select` returns a value for one.
+    ///
+    /// Valid frames of other kinds, such as measurements sent in active mode
+    /// while waiting for a different response, are skipped. An invalid frame
+    /// is returned as an error, since it may have been the response.
+    fn read_frame<T>(&mut self, select: impl Fn(Frame) -> Option<T>) -> Result<T, Error<U::Error>> {
+        let mut byte = [0_u8; 1];
+        // Frames left in the parser by an earlier call are checked before reading.
+        let mut read = 0;
+        loop {
+            for frame in self.parser.feed(&byte[..read]) {
+                if let Some(value) = select(frame?) {
+                    return Ok(value);
+                }
+            }
+            read = self.uart.read(&mut byte).map_err(Error::Bus)?;
+            if read == 0 {
+                return Err(Error::UnexpectedEof);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    extern crate std;
+    use std::{collections::VecDeque, vec::Vec};
+
+    use super::*;
+    use crate::i2c::test::{MEASUREMENT, MODULE};
+
+    /// A serial port which replays canned bytes and records what was written.
+    ///
+    /// The async traits are implemented alongside the async driver's tests.
+    #[derive(Default)]
+    pub(crate) struct FakeUart {
+        pub(crate) rx: VecDeque<u8>,
+        pub(crate) tx: Vec<u8>,
+    }
+
+    impl FakeUart {
+        pub(crate) fn read_rx(&mut self, buf: &mut [u8]) -> usize {
+            let count = buf.len().min(self.rx.len());
+            for (dest, src) in buf.iter_mut().zip(self.rx.drain(..count)) {
+                *dest = src;
+            }
+            count
+        }
+    }
+
+    impl embedded_io::ErrorType for FakeUart {
+        type Error = core::convert::Infallible;
+    }
+
+    impl Read for FakeUart {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            Ok(self.read_rx(buf))
+        }
+    }
+
+    impl Write for FakeUart {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.tx.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn request_measurement() {
+        let mut uart = FakeUart::default();
+        uart.rx.extend(MEASUREMENT);
+        let mut sensor = Apc1UartSensor::new(uart);
+
+        let measurement = sensor.request_measurement().unwrap();
+
+        assert_eq!(measurement.aqi, 1);
+        assert_eq!(
+            sensor.release().tx,
+            Command::RequestMeasurement.to_bytes().as_slice()
+        );
+    }
+
+    #[test]
+    fn read_measurement_skips_garbage() {
+        let mut uart = FakeUart::default();
+        // Line noise, a stray header byte, and a command reply precede the measurement.
+        uart.rx.extend([0x00, 0x13, 0x4D, 0x37]);
+        uart.rx
+            .extend([0x42, 0x42, 0x4D, 0x00, 0x04, 0xE1, 0x00, 0x01, 0x74]);
+        uart.rx.extend(MEASUREMENT);
+        let mut sensor = Apc1UartSensor::new(uart);
+
+        let measurement = sensor.read_measurement().unwrap();
+
+        assert_eq!(measurement.aqi, 1);
+        assert!(sensor.release().rx.is_empty());
+    }
+
+    #[test]
+    fn module() {
+        let mut uart = FakeUart::default();
+        uart.rx.extend(MEASUREMENT);
+        uart.rx.extend(MODULE);
+        let mut sensor = Apc1UartSensor::new(uart);
+
+        let module = sensor.module().unwrap();
+
+        assert_eq!(module.name_and_type.as_str(), "APC1-I");
+        assert_eq!(
+            sensor.release().tx,
+            Command::ReadModuleId.to_bytes().as_slice()
+        );
+    }
+
+    #[test]
+    fn set_measurement_mode() {
+        let mut sensor = Apc1UartSensor::new(FakeUart::default());
+
+        sensor
+            .set_measurement_mode(MeasurementMode::Active)
+            .unwrap();
+        sensor
+            .set_measurement_mode(MeasurementMode::Passive)
+            .unwrap();
+
+        let mut expected = Command::SetActiveMeasurement.to_bytes().to_vec();
+        expected.extend(Command::SetPassiveMeasurement.to_bytes());
+        assert_eq!(sensor.release().tx, expected);
+    }
+
+    #[test]
+    fn eof() {
+        let mut uart = FakeUart::default();
+        uart.rx.extend(&MEASUREMENT[..32]);
+        let mut sensor = Apc1UartSensor::new(uart);
+
+        assert_eq!(sensor.read_measurement(), Err(Error::UnexpectedEof));
+    }
+}
@@ -0,0 +1,165 @@
+//! A simulated APC1-I on an I2C bus.
+use apc1_core::i2c::{self, Command};
+use embedded_hal::i2c::{ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation};
+
+use crate::Simulator;
+
+/// An I2C bus with a simulated APC1-I at [`i2c::DEVICE_ADDR`].
+///
+/// The first byte written in a transaction selects a register, and the bytes
+/// after it are written to that register and those following it. Commands
+/// are carried out once the last command register is written. Reading
+/// without selecting a register returns a measurement, while reading from
+/// the response registers returns the module ID once it has been requested.
+#[derive(Debug, Clone)]
+pub struct MockI2c {
+    simulator: Simulator,
+    command: [u8; 7],
+    response: [u8; 23],
+}
+
+impl MockI2c {
+    pub fn new(simulator: Simulator) -> Self {
+        Self {
+            simulator,
+            command: [0; 7],
+            response: [0; 23],
+        }
+    }
+
+    pub fn simulator(&self) -> &Simulator {
+        &self.simulator
+    }
+
+    /// Change what the sensor reports from now on.
+    pub fn simulator_mut(&mut self) -> &mut Simulator {
+        &mut self.simulator
+    }
+
+    fn write_register(&mut self, register: u8, byte: u8) {
+        let Some(offset) = register
+            .checked_sub(i2c::COMMAND_BASE_ADDR)
+            .map(usize::from)
+            .filter(|&offset| offset < self.command.len())
+        else {
+            return;
+        };
+        self.command[offset] = byte;
+        if offset == self.command.len() - 1 {
+            self.execute();
+        }
+    }
+
+    fn read_register(&self, register: u8) -> u8 {
+        register
+            .checked_sub(i2c::RESPONSE_BASE_ADDR)
+            .and_then(|offset| self.response.get(usize::from(offset)))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Carry out the command in the command registers. Anything else,
+    /// including a command with a bad checksum, is ignored.
+    fn execute(&mut self) {
+        let command = self.command;
+        if command == Command::ReadModuleId.to_bytes() {
+            self.response = self.simulator.module_frame();
+        } else if command == Command::SetIdleMode.to_bytes() {
+            self.simulator.idle = true;
+        } else if command == Command::SetActiveMode.to_bytes()
+            || command == Command::Reset.to_bytes()
+        {
+            self.simulator.idle = false;
+        }
+    }
+}
+
+impl ErrorType for MockI2c {
+    type Error = ErrorKind;
+}
+
+impl I2c for MockI2c {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if address != i2c::DEVICE_ADDR {
+            return Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address));
+        }
+
+        let mut register = None;
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => {
+                    for &byte in bytes.iter() {
+                        register = match register {
+                            None => Some(byte),
+                            Some(register) => {
+                                self.write_register(register, byte);
+                                Some(register.wrapping_add(1))
+                            }
+                        };
+                    }
+                }
+                Operation::Read(buf) => match register {
+                    Some(start) => {
+                        for (register, byte) in (start..).zip(buf.iter_mut()) {
+                            *byte = self.read_register(register);
+                        }
+                        register = Some(start.wrapping_add(buf.len() as u8));
+                    }
+                    None => {
+                        let frame = self.simulator.measurement_frame();
+                        buf.fill(0);
+                        for (byte, value) in buf.iter_mut().zip(frame) {
+                            *byte = value;
+                        }
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use apc1_core::DeviceErrorCode;
+    use apc1_embedded::{Apc1Sensor, Error};
+
+    use super::*;
+
+    #[test]
+    fn driver() {
+        let mut simulator = Simulator::new();
+        simulator.readings.tvoc = 250;
+        simulator.module.serial_number = 42;
+        let mut sensor = Apc1Sensor::new(MockI2c::new(simulator));
+
+        assert_eq!(sensor.module().unwrap().serial_number, 42);
+        assert_eq!(sensor.measurement().unwrap().tvoc, 250);
+
+        let sensor = sensor.into_idle().map_err(|(_, e)| e).unwrap();
+        let mut bus = sensor.release();
+        assert!(bus.simulator().is_idle());
+
+        bus.simulator_mut().faults = DeviceErrorCode::from_bits(0x10);
+        let mut sensor = Apc1Sensor::new(bus);
+        assert!(matches!(
+            sensor.measurement(),
+            Err(Error::Response(apc1_core::Error::Device(_)))
+        ));
+        let sensor = sensor.reset().map_err(|(_, e)| e).unwrap();
+        assert!(!sensor.release().simulator().is_idle());
+    }
+
+    #[test]
+    fn wrong_address() {
+        let mut bus = MockI2c::new(Simulator::new());
+        assert_eq!(
+            bus.read(0x13, &mut [0; 64]),
+            Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address))
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! A simulated APC1, for developing and testing without a sensor.
+//!
+//! [`Simulator`] holds the values the sensor reports and the faults to
+//! inject, and encodes them into frames with [`Measurement::to_bytes`] and
+//! [`Module::to_bytes`]. [`MockI2c`] answers like an APC1-I on an
+//! [`embedded_hal::i2c::I2c`] bus, and [`MockUart`] is the far end of an
+//! APC1-U's serial port, through both the [`embedded_io`] and [`std::io`]
+//! traits.
+//!
+//! ```
+//! use apc1_embedded::Apc1Sensor;
+//! use apc1_mock::{MockI2c, Simulator};
+//!
+//! let mut simulator = Simulator::new();
+//! simulator.readings.pm2_5 = 12;
+//! let mut sensor = Apc1Sensor::new(MockI2c::new(simulator));
+//! assert_eq!(sensor.measurement().unwrap().pm2_5, 12);
+//! ```
+use apc1_core::{DeviceErrorCode, Measurement, Module, ModuleName};
+
+mod i2c;
+mod uart;
+
+pub use i2c::MockI2c;
+pub use uart::MockUart;
+
+/// The frame header that starts every response.
+const HEADER: [u8; 2] = [0x42, 0x4D];
+
+/// The state of a simulated sensor.
+///
+/// By default, it's an APC1-I in a clean room at 20.2°C.
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    /// The values reported in every measurement.
+    pub readings: Measurement,
+    pub module: Module,
+    /// Faults reported in every measurement, which the device sends in place
+    /// of the usual zero byte.
+    pub faults: DeviceErrorCode,
+    /// The number of frames still to be sent with a bad checksum.
+    corrupt_frames: usize,
+    idle: bool,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self {
+            readings: Measurement::builder()
+                .um_0_3_particles(336)
+                .um_0_5_particles(100)
+                .um_1_particles(20)
+                .um_2_5_particles(10)
+                .tvoc(37)
+                .eco2(429)
+                .t_comp(202)
+                .rh_comp(581)
+                .t_raw(251)
+                .rh_raw(437)
+                .rs_0(222_610)
+                .rs_1(1)
+                .rs_2(791_504)
+                .rs_3(37_734)
+                .aqi(1)
+                .version(35)
+                .build(),
+            module: Module::builder()
+                .name_and_type(ModuleName::from_bytes(*b"APC1-I"))
+                .serial_number(607_609_401_092_424_838)
+                .delimiter('-')
+                .fw_version_minor(35)
+                .build(),
+            faults: DeviceErrorCode::default(),
+            corrupt_frames: 0,
+            idle: false,
+        }
+    }
+}
+
+impl Simulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send the next `frames` frames, of any kind, with a bad checksum.
+    pub fn corrupt_checksums(&mut self, frames: usize) {
+        self.corrupt_frames = frames;
+    }
+
+    /// Whether the host has powered down the fan.
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    /// Encode the next measurement frame.
+    pub fn measurement_frame(&mut self) -> [u8; 64] {
+        let mut frame = self.readings.to_bytes_with_faults(self.faults);
+        self.corrupt(&mut frame);
+        frame
+    }
+
+    /// Encode the next module ID frame.
+    pub fn module_frame(&mut self) -> [u8; 23] {
+        let mut frame = self.module.to_bytes();
+        self.corrupt(&mut frame);
+        frame
+    }
+
+    /// Break the frame's checksum if more frames are to be corrupted.
+    fn corrupt(&mut self, frame: &mut [u8]) {
+        if self.corrupt_frames > 0 {
+            self.corrupt_frames -= 1;
+            let (_, checksum) = frame.split_at_mut(frame.len() - 2);
+            let sum = u16::from_be_bytes([checksum[0], checksum[1]]).wrapping_add(1);
+            checksum.copy_from_slice(&sum.to_be_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use apc1_core::{DeviceFault, Error};
+
+    use super::*;
+
+    #[test]
+    fn frames_parse() {
+        let mut simulator = Simulator::new();
+        simulator.readings.eco2 = 1_200;
+        simulator.readings.rs_3 = 70_000;
+
+        let measurement = Measurement::try_from(&simulator.measurement_frame()).unwrap();
+        assert_eq!(measurement.eco2, 1_200);
+        assert_eq!(measurement.rs_3, 70_000);
+        assert_eq!(measurement.temperature().celsius(), 20.2);
+        assert_eq!(measurement.version, 35);
+
+        let module = Module::try_from(&simulator.module_frame()).unwrap();
+        assert_eq!(module.name_and_type.as_str(), "APC1-I");
+        assert_eq!(module.serial_number, 607_609_401_092_424_838);
+        assert_eq!(module.fw_version_minor, 35);
+    }
+
+    #[test]
+    fn faults() {
+        let mut simulator = Simulator::new();
+        simulator.corrupt_checksums(2);
+        assert!(matches!(
+            Module::try_from(&simulator.module_frame()),
+            Err(Error::Checksum { .. })
+        ));
+        assert!(matches!(
+            Measurement::try_from(&simulator.measurement_frame()),
+            Err(Error::Checksum { .. })
+        ));
+        assert!(Measurement::try_from(&simulator.measurement_frame()).is_ok());
+
+        simulator.faults = DeviceErrorCode::from_bits(0x08);
+        let Err(Error::Device(code)) = Measurement::try_from(&simulator.measurement_frame()) else {
+            panic!("expected a device error");
+        };
+        assert!(code.contains(DeviceFault::FanStopped));
+    }
+}
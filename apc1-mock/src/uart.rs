@@ -0,0 +1,203 @@
+//! A simulated APC1-U on a serial port.
+use std::{collections::VecDeque, convert::Infallible};
+
+use apc1_core::uart::Command;
+
+use crate::{Simulator, HEADER};
+
+/// The far end of a serial port with a simulated APC1-U attached.
+///
+/// Bytes written to it are read as commands, and the device's responses are
+/// queued to be read back. Like the device, it starts in passive measurement
+/// mode and only sends a measurement when asked; in active mode, a fresh
+/// measurement is ready whenever the queue is empty rather than once a
+/// second. Reading with nothing queued reports end-of-file, rather than
+/// blocking forever.
+#[derive(Debug, Clone)]
+pub struct MockUart {
+    simulator: Simulator,
+    /// Bytes from the host that don't yet form a complete command.
+    received: Vec<u8>,
+    /// Bytes waiting to be read by the host.
+    sending: VecDeque<u8>,
+    active_measurement: bool,
+}
+
+impl MockUart {
+    pub fn new(simulator: Simulator) -> Self {
+        Self {
+            simulator,
+            received: Vec::new(),
+            sending: VecDeque::new(),
+            active_measurement: false,
+        }
+    }
+
+    pub fn simulator(&self) -> &Simulator {
+        &self.simulator
+    }
+
+    /// Change what the sensor reports from now on.
+    pub fn simulator_mut(&mut self) -> &mut Simulator {
+        &mut self.simulator
+    }
+
+    /// Queue bytes for the host to read, such as noise on the line.
+    pub fn send(&mut self, bytes: &[u8]) {
+        self.sending.extend(bytes);
+    }
+
+    fn receive(&mut self, bytes: &[u8]) {
+        self.received.extend_from_slice(bytes);
+        loop {
+            match self
+                .received
+                .windows(HEADER.len())
+                .position(|window| window == HEADER)
+            {
+                Some(start) => {
+                    self.received.drain(..start);
+                }
+                None => {
+                    // Keep a final byte that may be the start of a header.
+                    let keep = usize::from(self.received.last() == Some(&HEADER[0]));
+                    self.received.drain(..self.received.len() - keep);
+                    return;
+                }
+            }
+            let Some(command) = self.received.first_chunk::<7>().copied() else {
+                return;
+            };
+            self.received.drain(..command.len());
+            self.execute(command);
+        }
+    }
+
+    /// Carry out a command. Anything else, including a command with a bad
+    /// checksum, is ignored.
+    fn execute(&mut self, command: [u8; 7]) {
+        if command == Command::RequestMeasurement.to_bytes() {
+            let frame = self.simulator.measurement_frame();
+            self.sending.extend(frame);
+        } else if command == Command::ReadModuleId.to_bytes() {
+            let frame = self.simulator.module_frame();
+            self.sending.extend(frame);
+        } else if command == Command::SetActiveMeasurement.to_bytes() {
+            self.active_measurement = true;
+        } else if command == Command::SetPassiveMeasurement.to_bytes() {
+            self.active_measurement = false;
+        } else if command == Command::SetIdleMode.to_bytes() {
+            self.simulator.idle = true;
+        } else if command == Command::SetActiveMode.to_bytes() {
+            self.simulator.idle = false;
+        }
+    }
+
+    fn transmit(&mut self, buf: &mut [u8]) -> usize {
+        if self.sending.is_empty() && self.active_measurement && !self.simulator.idle {
+            let frame = self.simulator.measurement_frame();
+            self.sending.extend(frame);
+        }
+        let count = buf.len().min(self.sending.len());
+        for (dest, byte) in buf.iter_mut().zip(self.sending.drain(..count)) {
+            *dest = byte;
+        }
+        count
+    }
+}
+
+impl embedded_io::ErrorType for MockUart {
+    type Error = Infallible;
+}
+
+impl embedded_io::Read for MockUart {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(self.transmit(buf))
+    }
+}
+
+impl embedded_io::Write for MockUart {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.receive(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl std::io::Read for MockUart {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        Ok(self.transmit(buf))
+    }
+}
+
+impl std::io::Write for MockUart {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.receive(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use apc1_core::ModuleName;
+    use apc1_embedded::{Apc1UartSensor, Error, MeasurementMode};
+
+    use super::*;
+
+    #[test]
+    fn driver() {
+        let mut simulator = Simulator::new();
+        simulator.module.name_and_type = ModuleName::from_bytes(*b"APC1-U");
+        simulator.readings.pm10 = 80;
+        let mut uart = MockUart::new(simulator);
+        uart.send(&[0x00, 0x42, 0x13]);
+        let mut sensor = Apc1UartSensor::new(uart);
+
+        assert_eq!(sensor.module().unwrap().name_and_type.as_str(), "APC1-U");
+        assert_eq!(sensor.request_measurement().unwrap().pm10, 80);
+
+        sensor
+            .set_measurement_mode(MeasurementMode::Active)
+            .unwrap();
+        assert_eq!(sensor.read_measurement().unwrap().pm10, 80);
+        assert_eq!(sensor.read_measurement().unwrap().pm10, 80);
+
+        sensor
+            .set_measurement_mode(MeasurementMode::Passive)
+            .unwrap();
+        assert_eq!(sensor.read_measurement(), Err(Error::UnexpectedEof));
+    }
+
+    #[test]
+    fn corrupt_checksum() {
+        let mut simulator = Simulator::new();
+        simulator.corrupt_checksums(1);
+        let mut sensor = Apc1UartSensor::new(MockUart::new(simulator));
+
+        assert!(matches!(
+            sensor.request_measurement(),
+            Err(Error::Response(apc1_core::Error::Checksum { .. }))
+        ));
+        assert!(sensor.request_measurement().is_ok());
+    }
+
+    #[test]
+    fn split_commands() {
+        let mut uart = MockUart::new(Simulator::new());
+        let command = Command::ReadModuleId.to_bytes();
+        embedded_io::Write::write(&mut uart, &[0x4D, 0x42]).unwrap();
+        embedded_io::Write::write(&mut uart, &command[1..4]).unwrap();
+        embedded_io::Write::write(&mut uart, &command[4..]).unwrap();
+
+        let mut frame = [0; 23];
+        assert_eq!(embedded_io::Read::read(&mut uart, &mut frame), Ok(23));
+        assert!(apc1_core::Module::try_from(&frame).is_ok());
+    }
+}